@@ -0,0 +1,238 @@
+//! Classification of the changes between two sets of metric declarations.
+//!
+//! Metrics are matched by name across all modules, since that's what dashboards and alerts refer
+//! to: moving a metric to another module is not a change.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use necessary_metrics_core::catalog::{Catalog, CatalogMetric};
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Change {
+    pub(crate) metric: String,
+    pub(crate) kind: ChangeKind,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ChangeKind {
+    MetricAdded,
+    MetricRemoved,
+    KindChanged {
+        old: String,
+        new: String,
+    },
+    UnitChanged {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    LabelAdded(String),
+    LabelRemoved(String),
+    LabelValueAdded {
+        label: String,
+        value: String,
+    },
+    LabelValueRemoved {
+        label: String,
+        value: String,
+    },
+    DescriptionChanged,
+}
+
+impl Change {
+    /// Whether existing queries on the metric may stop working, or silently change meaning.
+    pub(crate) fn is_breaking(&self) -> bool {
+        match self.kind {
+            ChangeKind::MetricRemoved
+            | ChangeKind::KindChanged { .. }
+            | ChangeKind::UnitChanged { .. }
+            | ChangeKind::LabelRemoved(_)
+            | ChangeKind::LabelValueRemoved { .. } => true,
+            ChangeKind::MetricAdded
+            | ChangeKind::LabelAdded(_)
+            | ChangeKind::LabelValueAdded { .. }
+            | ChangeKind::DescriptionChanged => false,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = if self.is_breaking() {
+            "breaking"
+        } else {
+            "non-breaking"
+        };
+        write!(f, "{severity}: metric `{}` ", self.metric)?;
+        match &self.kind {
+            ChangeKind::MetricAdded => write!(f, "added"),
+            ChangeKind::MetricRemoved => write!(f, "removed"),
+            ChangeKind::KindChanged { old, new } => write!(f, "kind changed from {old} to {new}"),
+            ChangeKind::UnitChanged { old, new } => write!(
+                f,
+                "unit changed from {} to {}",
+                old.as_deref().unwrap_or("none"),
+                new.as_deref().unwrap_or("none")
+            ),
+            ChangeKind::LabelAdded(label) => write!(f, "label `{label}` added"),
+            ChangeKind::LabelRemoved(label) => write!(f, "label `{label}` removed"),
+            ChangeKind::LabelValueAdded { label, value } => {
+                write!(f, "label `{label}` value `{value}` added")
+            }
+            ChangeKind::LabelValueRemoved { label, value } => {
+                write!(f, "label `{label}` value `{value}` removed")
+            }
+            ChangeKind::DescriptionChanged => write!(f, "description changed"),
+        }
+    }
+}
+
+/// Lists the changes from `old` to `new`, ordered by metric name.
+pub(crate) fn diff(old: &[Catalog], new: &[Catalog]) -> Vec<Change> {
+    let old = by_name(old);
+    let new = by_name(new);
+    let mut changes = Vec::new();
+
+    for (name, old_metric) in &old {
+        let change = |kind| Change {
+            metric: name.to_string(),
+            kind,
+        };
+        let Some(new_metric) = new.get(name) else {
+            changes.push(change(ChangeKind::MetricRemoved));
+            continue;
+        };
+
+        if old_metric.kind != new_metric.kind {
+            changes.push(change(ChangeKind::KindChanged {
+                old: old_metric.kind.as_str().to_owned(),
+                new: new_metric.kind.as_str().to_owned(),
+            }));
+        }
+        if old_metric.unit != new_metric.unit {
+            changes.push(change(ChangeKind::UnitChanged {
+                old: old_metric.unit.map(str::to_owned),
+                new: new_metric.unit.map(str::to_owned),
+            }));
+        }
+        for old_label in &old_metric.labels {
+            let Some(new_label) = new_metric.labels.iter().find(|l| l.key == old_label.key) else {
+                changes.push(change(ChangeKind::LabelRemoved(old_label.key.clone())));
+                continue;
+            };
+            // Value domains are only comparable when both sides declare one.
+            if let (Some(old_values), Some(new_values)) = (&old_label.values, &new_label.values) {
+                for value in old_values.iter().filter(|v| !new_values.contains(v)) {
+                    changes.push(change(ChangeKind::LabelValueRemoved {
+                        label: old_label.key.clone(),
+                        value: value.clone(),
+                    }));
+                }
+                for value in new_values.iter().filter(|v| !old_values.contains(v)) {
+                    changes.push(change(ChangeKind::LabelValueAdded {
+                        label: old_label.key.clone(),
+                        value: value.clone(),
+                    }));
+                }
+            }
+        }
+        for new_label in &new_metric.labels {
+            if !old_metric.labels.iter().any(|l| l.key == new_label.key) {
+                changes.push(change(ChangeKind::LabelAdded(new_label.key.clone())));
+            }
+        }
+        if old_metric.description != new_metric.description {
+            changes.push(change(ChangeKind::DescriptionChanged));
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        changes.push(Change {
+            metric: name.to_string(),
+            kind: ChangeKind::MetricAdded,
+        });
+    }
+    changes.sort_by(|a, b| a.metric.cmp(&b.metric));
+
+    changes
+}
+
+fn by_name(catalogs: &[Catalog]) -> BTreeMap<&str, &CatalogMetric> {
+    let mut metrics = BTreeMap::new();
+    for metric in catalogs.iter().flat_map(|catalog| &catalog.metrics) {
+        metrics.entry(metric.name.as_str()).or_insert(metric);
+    }
+
+    metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use necessary_metrics_core::Mod;
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    fn render(old: Mod, new: Mod) -> Vec<String> {
+        let old = [Catalog::new(None, &old)];
+        let new = [Catalog::new(None, &new)];
+        diff(&old, &new).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn no_changes() {
+        let mod_ = || -> Mod {
+            parse_quote! {
+                mod metrics {
+                    #[description = "requests"]
+                    pub fn requests(route: &str) -> Counter;
+                }
+            }
+        };
+        assert!(render(mod_(), mod_()).is_empty());
+    }
+
+    #[test]
+    fn classifies_changes() {
+        let old = parse_quote! {
+            mod metrics {
+                #[description = "requests"]
+                pub fn requests(route: &str, host: &str) -> Counter;
+
+                #[description = "latency"]
+                #[unit = metrics::Unit::Seconds]
+                pub fn latency(#[values("get", "put")] method: &str) -> Histogram;
+
+                pub fn in_flight() -> Counter;
+                pub fn restarts() -> Counter;
+            }
+        };
+        let new = parse_quote! {
+            mod metrics {
+                #[description = "served requests"]
+                pub fn requests(route: &str, region: &str) -> Counter;
+
+                #[description = "latency"]
+                #[unit = metrics::Unit::Milliseconds]
+                pub fn latency(#[values("get", "post")] method: &str) -> Histogram;
+
+                pub fn in_flight() -> Gauge;
+                pub fn errors() -> Counter;
+            }
+        };
+
+        assert_eq!(
+            render(old, new),
+            [
+                "non-breaking: metric `errors` added",
+                "breaking: metric `in_flight` kind changed from counter to gauge",
+                "breaking: metric `latency` unit changed from seconds to milliseconds",
+                "breaking: metric `latency` label `method` value `put` removed",
+                "non-breaking: metric `latency` label `method` value `post` added",
+                "breaking: metric `requests` label `host` removed",
+                "non-breaking: metric `requests` label `region` added",
+                "non-breaking: metric `requests` description changed",
+                "breaking: metric `restarts` removed",
+            ]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Extracts metric declarations from `#[necessary_metrics]` modules straight from the sources,
+//! using the same parser as the macro, so catalogs and docs can be produced without building the
+//! crate that declares them.
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use necessary_metrics_core::catalog::{Catalog, CatalogFormat};
+
+mod diff;
+mod source;
+mod table;
+
+const USAGE: &str = "\
+Usage: necessary-metrics-cli list [--format <FORMAT>] <PATH>...
+       necessary-metrics-cli diff <OLD_PATH> <NEW_PATH>
+
+Commands:
+  list    Print the metrics declared in the given files or directories
+  diff    Compare the metrics declared in two files or directories, classifying each change as
+          breaking or non-breaking. Exits with status 1 if there are breaking changes
+
+Options:
+  --format <FORMAT>    Output format: table (default), json, yaml, markdown, grafana, or alerts
+  -h, --help           Print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Table,
+    Catalog(CatalogFormat),
+}
+
+#[derive(Debug)]
+enum Command {
+    List {
+        format: OutputFormat,
+        paths: Vec<PathBuf>,
+    },
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+    },
+    Help,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter();
+    let command = match args.next().as_deref() {
+        Some("list") => "list",
+        Some("diff") => "diff",
+        Some("-h" | "--help") | None => return Ok(Command::Help),
+        Some(other) => return Err(format!("unknown command `{other}`")),
+    };
+
+    let mut format = OutputFormat::Table;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--format" if command == "list" => {
+                format = match args.next().as_deref() {
+                    Some("table") => OutputFormat::Table,
+                    Some("json") => OutputFormat::Catalog(CatalogFormat::Json),
+                    Some("yaml") => OutputFormat::Catalog(CatalogFormat::Yaml),
+                    Some("markdown") => OutputFormat::Catalog(CatalogFormat::Markdown),
+                    Some("grafana") => OutputFormat::Catalog(CatalogFormat::Grafana),
+                    Some("alerts") => OutputFormat::Catalog(CatalogFormat::AlertRules),
+                    Some(other) => return Err(format!("unknown format `{other}`")),
+                    None => return Err("`--format` requires a value".to_owned()),
+                }
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{flag}`")),
+            path => paths.push(PathBuf::from(path)),
+        }
+    }
+
+    if command == "diff" {
+        let [old, new] = <[PathBuf; 2]>::try_from(paths)
+            .map_err(|_| "`diff` requires exactly two paths".to_owned())?;
+        return Ok(Command::Diff { old, new });
+    }
+    if paths.is_empty() {
+        return Err(format!("`{command}` requires at least one path"));
+    }
+
+    Ok(Command::List { format, paths })
+}
+
+/// Renders all catalogs as a single document in `format`: a JSON array (of catalogs or Grafana
+/// dashboards), a multi-document YAML stream, a rule file, or concatenated Markdown pages and
+/// tables.
+fn render(catalogs: &[Catalog], format: OutputFormat) -> String {
+    match format {
+        OutputFormat::Table => catalogs
+            .iter()
+            .map(table::render)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        OutputFormat::Catalog(format @ (CatalogFormat::Json | CatalogFormat::Grafana)) => {
+            let catalogs: Vec<_> = catalogs
+                .iter()
+                .map(|catalog| catalog.render(format).trim_end().to_owned())
+                .collect();
+            format!("[{}]\n", catalogs.join(",\n"))
+        }
+        OutputFormat::Catalog(CatalogFormat::Yaml) => catalogs
+            .iter()
+            .map(|catalog| format!("---\n{}", catalog.render(CatalogFormat::Yaml)))
+            .collect(),
+        // A single rule file, with a group per module.
+        OutputFormat::Catalog(CatalogFormat::AlertRules) => {
+            let groups: String = catalogs
+                .iter()
+                .map(|catalog| {
+                    let rules = catalog.render(CatalogFormat::AlertRules);
+                    rules.strip_prefix("groups:\n").unwrap_or(&rules).to_owned()
+                })
+                .collect();
+            if groups.is_empty() {
+                "groups: []\n".to_owned()
+            } else {
+                format!("groups:\n{groups}")
+            }
+        }
+        OutputFormat::Catalog(CatalogFormat::Markdown) => catalogs
+            .iter()
+            .map(|catalog| catalog.render(CatalogFormat::Markdown))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+fn main() -> ExitCode {
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    match command {
+        Command::Help => {
+            print!("{USAGE}");
+            ExitCode::SUCCESS
+        }
+        Command::List { format, paths } => {
+            let (modules, errors) = source::scan(&paths);
+            for error in &errors {
+                eprintln!("{error}");
+            }
+
+            let catalogs: Vec<_> = modules
+                .iter()
+                .map(|module| Catalog::new(module.crate_name.clone(), &module.mod_))
+                .collect();
+            print!("{}", render(&catalogs, format));
+
+            if errors.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Command::Diff { old, new } => {
+            // Like diff(1), trouble is reported with status 2, as a partial parse would show
+            // spurious removals.
+            let (Some(old), Some(new)) = (catalogs(old), catalogs(new)) else {
+                return ExitCode::from(2);
+            };
+
+            let changes = diff::diff(&old, &new);
+            for change in &changes {
+                println!("{change}");
+            }
+
+            if changes.iter().any(diff::Change::is_breaking) {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+    }
+}
+
+/// Scans `path` for catalogs, reporting errors to stderr. Returns `None` if there were any.
+fn catalogs(path: PathBuf) -> Option<Vec<Catalog>> {
+    let (modules, errors) = source::scan(&[path]);
+    for error in &errors {
+        eprintln!("{error}");
+    }
+    if !errors.is_empty() {
+        return None;
+    }
+
+    Some(
+        modules
+            .iter()
+            .map(|module| Catalog::new(module.crate_name.clone(), &module.mod_))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_list() {
+        let Ok(Command::List { format, paths }) = args(&["list", "--format", "json", "src"]) else {
+            panic!("expected `list` command");
+        };
+        assert_eq!(format, OutputFormat::Catalog(CatalogFormat::Json));
+        assert_eq!(paths, [PathBuf::from("src")]);
+    }
+
+    #[test]
+    fn parses_diff() {
+        let Ok(Command::Diff { old, new }) = args(&["diff", "old/src", "new/src"]) else {
+            panic!("expected `diff` command");
+        };
+        assert_eq!(old, PathBuf::from("old/src"));
+        assert_eq!(new, PathBuf::from("new/src"));
+    }
+
+    #[test]
+    fn rejects_bad_args() {
+        assert!(args(&["list"]).is_err());
+        assert!(args(&["diff", "old/src"]).is_err());
+        assert!(args(&["diff", "--format", "json", "old/src", "new/src"]).is_err());
+        assert!(args(&["list", "--format", "xml", "src"]).is_err());
+        assert!(args(&["frobnicate"]).is_err());
+    }
+}
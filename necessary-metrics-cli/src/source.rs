@@ -0,0 +1,276 @@
+//! Discovery of `#[necessary_metrics]` modules in Rust sources, without compiling them.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use necessary_metrics_core::{MacroArgs, Mod};
+use quote::ToTokens;
+use syn::{Item, ItemMod, Meta};
+
+/// A `#[necessary_metrics]` module found in a source file.
+#[derive(Debug)]
+pub(crate) struct SourceModule {
+    /// Name of the crate the file belongs to, as `CARGO_CRATE_NAME` would report it.
+    pub(crate) crate_name: Option<String>,
+    pub(crate) mod_: Mod,
+}
+
+/// An error in a source file, located like rustc does.
+#[derive(Debug)]
+pub(crate) struct SourceError {
+    path: PathBuf,
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: error: {}",
+            self.path.display(),
+            self.line,
+            self.column,
+            self.message
+        )
+    }
+}
+
+impl SourceError {
+    fn from_syn(path: &Path, error: syn::Error) -> Vec<Self> {
+        error
+            .into_iter()
+            .map(|error| {
+                let start = error.span().start();
+                SourceError {
+                    path: path.to_owned(),
+                    line: start.line,
+                    column: start.column + 1,
+                    message: error.to_string(),
+                }
+            })
+            .collect()
+    }
+
+    fn from_io(path: &Path, error: std::io::Error) -> Self {
+        SourceError {
+            path: path.to_owned(),
+            line: 0,
+            column: 0,
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Scans `paths`, which may be files or directories, for `#[necessary_metrics]` modules.
+/// Directories are walked recursively in sorted order, so the output is deterministic. Errors
+/// don't stop the scan; they're returned alongside whatever modules could be parsed.
+pub(crate) fn scan(paths: &[PathBuf]) -> (Vec<SourceModule>, Vec<SourceError>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        if let Err(e) = collect_rs_files(path, &mut files) {
+            errors.push(SourceError::from_io(path, e));
+        }
+    }
+
+    let mut modules = Vec::new();
+    for file in files {
+        match std::fs::read_to_string(&file) {
+            Ok(src) => {
+                let crate_name = crate_name(&file);
+                let (mut file_modules, file_errors) = scan_src(&file, crate_name, &src);
+                modules.append(&mut file_modules);
+                errors.extend(file_errors);
+            }
+            Err(e) => errors.push(SourceError::from_io(&file, e)),
+        }
+    }
+
+    (modules, errors)
+}
+
+pub(crate) fn scan_src(
+    path: &Path,
+    crate_name: Option<String>,
+    src: &str,
+) -> (Vec<SourceModule>, Vec<SourceError>) {
+    let mut modules = Vec::new();
+    let mut errors = Vec::new();
+    match syn::parse_file(src) {
+        Ok(file) => visit_items(path, &crate_name, file.items, &mut modules, &mut errors),
+        Err(e) => errors.extend(SourceError::from_syn(path, e)),
+    }
+
+    (modules, errors)
+}
+
+fn visit_items(
+    path: &Path,
+    crate_name: &Option<String>,
+    items: Vec<Item>,
+    modules: &mut Vec<SourceModule>,
+    errors: &mut Vec<SourceError>,
+) {
+    for item in items {
+        let Item::Mod(mut item_mod) = item else {
+            continue;
+        };
+
+        match take_macro_attr(&mut item_mod) {
+            Some(args) => {
+                let parsed = args.and_then(|args| {
+                    let mut mod_ = syn::parse2::<Mod>(item_mod.to_token_stream())?;
+                    mod_.apply_args(&args)?;
+                    Ok(mod_)
+                });
+                match parsed {
+                    Ok(mod_) => modules.push(SourceModule {
+                        crate_name: crate_name.clone(),
+                        mod_,
+                    }),
+                    Err(e) => errors.extend(SourceError::from_syn(path, e)),
+                }
+            }
+            None => {
+                if let Some((_brace, items)) = item_mod.content {
+                    visit_items(path, crate_name, items, modules, errors);
+                }
+            }
+        }
+    }
+}
+
+/// Removes the `#[necessary_metrics]` attribute from `item_mod`, returning its parsed arguments.
+/// Both `#[necessary_metrics]` and qualified paths like
+/// `#[necessary_metrics::necessary_metrics]` are recognised.
+fn take_macro_attr(item_mod: &mut ItemMod) -> Option<syn::Result<MacroArgs>> {
+    let idx = item_mod.attrs.iter().position(|attr| {
+        attr.path()
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "necessary_metrics")
+    })?;
+    let attr = item_mod.attrs.remove(idx);
+
+    Some(match attr.meta {
+        Meta::Path(_) => Ok(MacroArgs::default()),
+        Meta::List(list) => syn::parse2(list.tokens),
+        Meta::NameValue(mnv) => Err(syn::Error::new_spanned(
+            mnv,
+            "Expected `#[necessary_metrics]` or `#[necessary_metrics(...)]`",
+        )),
+    })
+}
+
+fn collect_rs_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        // Explicitly given files are scanned whatever their extension.
+        files.push(path.to_owned());
+        return Ok(());
+    }
+
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        let name = entry.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with('.') || name == "target" {
+            continue;
+        }
+        if entry.is_dir() {
+            collect_rs_files(&entry, files)?;
+        } else if entry.extension().is_some_and(|ext| ext == "rs") {
+            files.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// Finds the package name in the closest `Cargo.toml` above `path`, normalised the way Cargo
+/// does for `CARGO_CRATE_NAME`. This is a line-based read, not a TOML parser, which is enough for
+/// the `name = "..."` key every manifest has.
+fn crate_name(path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    let manifest = path
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest| manifest.is_file())?;
+    let manifest = std::fs::read_to_string(manifest).ok()?;
+
+    let mut in_package = false;
+    for line in manifest.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if in_package {
+            if let Some(value) = line
+                .strip_prefix("name")
+                .and_then(|rest| rest.trim_start().strip_prefix('='))
+            {
+                return Some(value.trim().trim_matches('"').replace('-', "_"));
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SRC: &str = r#"
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(catalog = "yaml")]
+pub mod app_metrics {
+    #[description = "task latency"]
+    pub fn task_latency(task_name: &str) -> Histogram;
+}
+
+mod nested {
+    #[necessary_metrics::necessary_metrics]
+    mod more_metrics {
+        pub fn restarts() -> Counter;
+    }
+}
+
+mod unrelated {
+    pub fn helper() {}
+}
+"#;
+
+    #[test]
+    fn finds_annotated_modules() {
+        let (modules, errors) = scan_src(Path::new("lib.rs"), None, SRC);
+
+        assert!(errors.is_empty(), "{errors:?}");
+        let names: Vec<_> = modules.iter().map(|m| m.mod_.ident.to_string()).collect();
+        assert_eq!(names, ["app_metrics", "more_metrics"]);
+        assert_eq!(modules[0].mod_.fns[0].ident, "task_latency");
+    }
+
+    #[test]
+    fn errors_are_located() {
+        let src = r#"
+#[necessary_metrics]
+mod app_metrics {
+    pub fn task_latency() -> String;
+}
+"#;
+        let (modules, errors) = scan_src(Path::new("src/lib.rs"), None, src);
+
+        assert!(modules.is_empty());
+        let errors: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            ["src/lib.rs:4:30: error: Only `Counter`, `Gauge`, `Histogram`, `Summary`, `Info`, and `StateSet<State>` are allowed as return types on functions, where `Counter`, `Gauge` and `Histogram` may be qualified, as in `metrics::Counter`"]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Plain-text table rendering of a catalog, for terminals.
+
+use necessary_metrics_core::catalog::Catalog;
+
+const HEADER: [&str; 5] = ["NAME", "KIND", "UNIT", "LABELS", "DESCRIPTION"];
+
+pub(crate) fn render(catalog: &Catalog) -> String {
+    let rows: Vec<[String; 5]> = catalog
+        .metrics
+        .iter()
+        .map(|metric| {
+            let labels: Vec<_> = metric
+                .labels
+                .iter()
+                .map(|label| label.key.as_str())
+                .collect();
+            [
+                metric.name.clone(),
+                metric.kind.as_str().to_owned(),
+                metric.unit.unwrap_or("-").to_owned(),
+                if labels.is_empty() {
+                    "-".to_owned()
+                } else {
+                    labels.join(", ")
+                },
+                metric.description.clone().unwrap_or_else(|| "-".to_owned()),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADER.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    match &catalog.crate_name {
+        Some(crate_name) => out.push_str(&format!("{crate_name}::{}\n", catalog.module)),
+        None => out.push_str(&format!("{}\n", catalog.module)),
+    }
+    push_row(&mut out, &widths, HEADER.iter().copied());
+    for row in &rows {
+        push_row(&mut out, &widths, row.iter().map(String::as_str));
+    }
+
+    out
+}
+
+fn push_row<'a>(out: &mut String, widths: &[usize; 5], cells: impl Iterator<Item = &'a str>) {
+    let mut line = String::new();
+    for (i, (cell, width)) in cells.zip(widths).enumerate() {
+        if i > 0 {
+            line.push_str("  ");
+        }
+        line.push_str(cell);
+        line.extend(std::iter::repeat_n(' ', width - cell.chars().count()));
+    }
+    out.push_str(line.trim_end());
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use necessary_metrics_core::Mod;
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    #[test]
+    fn renders_aligned_columns() {
+        let mod_: Mod = parse_quote! {
+            mod app_metrics {
+                #[description = "task latency"]
+                #[unit = metrics::Unit::Seconds]
+                pub fn task_latency(task_name: &str, host: &str) -> Histogram;
+
+                pub fn restarts() -> Counter;
+            }
+        };
+        let catalog = Catalog::new(Some("my_service".to_owned()), &mod_);
+
+        let expected = "\
+my_service::app_metrics
+NAME          KIND       UNIT     LABELS           DESCRIPTION
+task_latency  histogram  seconds  task_name, host  task latency
+restarts      counter    -        -                -
+";
+        assert_eq!(render(&catalog), expected);
+    }
+}
@@ -0,0 +1,354 @@
+//! Prometheus alerting rules declared next to metrics with `#[alert(...)]`.
+//!
+//! An alert's `expr` is PromQL with a few shorthands, so that it refers to the metric without
+//! spelling out its name, and is renamed along with it:
+//!
+//! - `{metric}` expands to the metric name.
+//! - The function name expands to the metric name too, in case macro arguments like
+//!   `conventions` amend it.
+//! - `{labels}` expands to the metric's label keys, comma-separated, e.g. for `sum by ({labels})`.
+//! - On histograms, `pNN` (e.g. `p50`, `p99`, `p999`) expands to that quantile over the last
+//!   [`RATE_WINDOW`], keeping the metric's labels. On summaries, it selects the series of that
+//!   quantile, which must be one summaries report, from the `quantiles(...)` macro argument or
+//!   [`Quantiles::DEFAULT`](crate::Quantiles::DEFAULT).
+//!
+//! String literals, like label matcher values, are left as written.
+
+use std::fmt::Write;
+
+use crate::catalog::{json_str, Catalog};
+use crate::{FnReturnTy, ItemFn};
+
+/// Window rates are computed over in `pNN` shorthands.
+pub const RATE_WINDOW: &str = "5m";
+
+/// Expands the shorthands in an alert expression on `fn_`. References to the function name are
+/// renamed to the metric name, which macro arguments like `conventions` can amend. Errors if the
+/// expression doesn't refer to the metric, or uses a shorthand that doesn't apply to its kind.
+///
+/// `quantiles` are those summaries report, which `pNN` shorthands on summaries are checked
+/// against, if known.
+pub fn expand_expr(expr: &str, fn_: &ItemFn, quantiles: Option<&[f64]>) -> Result<String, String> {
+    let fn_name = fn_.ident.to_string();
+    let metric_name = fn_.metric_name.as_str();
+    let kind = &fn_.fn_return_ty;
+    let labels: Vec<_> = fn_.args.iter().map(|arg| arg.ident.to_string()).collect();
+    let labels = labels.join(", ");
+    let mut out = String::with_capacity(expr.len());
+    let mut refers_to_metric = false;
+
+    let mut rest = expr;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("{metric}") {
+            out.push_str(metric_name);
+            refers_to_metric = true;
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("{labels}") {
+            out.push_str(&labels);
+            rest = after;
+        } else if let '"' | '\'' | '`' = c {
+            // String literals, like label matcher values, are copied through unchanged.
+            let end = string_literal_len(rest, c);
+            out.push_str(&rest[..end]);
+            rest = &rest[end..];
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+            match quantile(word) {
+                Some(quantile) => {
+                    match kind {
+                        FnReturnTy::Histogram => {
+                            let by = if labels.is_empty() {
+                                "le".to_owned()
+                            } else {
+                                format!("le, {labels}")
+                            };
+                            let _ = write!(
+                                out,
+                                "histogram_quantile({quantile}, sum by ({by}) (rate({metric_name}_bucket[{RATE_WINDOW}])))"
+                            );
+                        }
+                        FnReturnTy::Summary => {
+                            if let Some(quantiles) =
+                                quantiles.filter(|quantiles| !quantiles.contains(&quantile))
+                            {
+                                let quantiles: Vec<_> =
+                                    quantiles.iter().map(f64::to_string).collect();
+                                return Err(format!(
+                                    "`{word}` isn't a quantile summaries report ({}); add {quantile} to the `quantiles(...)` macro argument",
+                                    quantiles.join(", "),
+                                ));
+                            }
+                            let _ = write!(
+                                out,
+                                "max by ({labels}) ({metric_name}{{quantile=\"{quantile}\"}})"
+                            );
+                        }
+                        FnReturnTy::Counter
+                        | FnReturnTy::Gauge
+                        | FnReturnTy::Info
+                        | FnReturnTy::StateSet(_) => {
+                            return Err(format!(
+                                "`{word}` is only available on histograms and summaries; use `{{metric}}` to refer to this metric"
+                            ));
+                        }
+                    }
+                    refers_to_metric = true;
+                }
+                None if word == fn_name || word == metric_name => {
+                    out.push_str(metric_name);
+                    refers_to_metric = true;
+                }
+                None => out.push_str(word),
+            }
+            rest = &rest[end..];
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    if !refers_to_metric {
+        return Err(
+            "Alert expression must refer to the metric, with `{metric}` or a `pNN` quantile"
+                .to_owned(),
+        );
+    }
+
+    Ok(out)
+}
+
+/// Length of the PromQL string literal `rest` starts with, quoted by `quote`, up to the end of
+/// `rest` if it isn't closed. Backslashes escape the next character, except in raw strings quoted
+/// by backticks.
+fn string_literal_len(rest: &str, quote: char) -> usize {
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return i + c.len_utf8();
+        }
+        if c == '\\' && quote != '`' {
+            chars.next();
+        }
+    }
+
+    rest.len()
+}
+
+/// Parses `pNN` quantile shorthands: `p99` is `0.99`, `p999` is `0.999`. Formatted like
+/// exporters format the `quantile` label of summaries, so `p50` is `0.5`.
+fn quantile(word: &str) -> Option<f64> {
+    let digits = word.strip_prefix('p')?;
+    if digits.len() < 2 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    format!("0.{digits}").parse().ok()
+}
+
+/// Renders the catalog's alerts as a Prometheus rule file with one group per module.
+pub(crate) fn rules(catalog: &Catalog) -> String {
+    let group = match &catalog.crate_name {
+        Some(crate_name) => format!("{crate_name}.{}", catalog.module),
+        None => catalog.module.clone(),
+    };
+
+    let mut out = String::new();
+    out.push_str("groups:\n");
+    let _ = writeln!(out, "  - name: {}", json_str(&group));
+
+    let alerts: Vec<_> = catalog
+        .metrics
+        .iter()
+        .flat_map(|metric| metric.alerts.iter().map(move |alert| (metric, alert)))
+        .collect();
+    if alerts.is_empty() {
+        out.push_str("    rules: []\n");
+        return out;
+    }
+
+    out.push_str("    rules:\n");
+    for (metric, alert) in alerts {
+        let _ = writeln!(out, "      - alert: {}", json_str(&alert.name));
+        let _ = writeln!(out, "        expr: {}", json_str(&alert.expr));
+        if let Some(for_) = &alert.for_ {
+            let _ = writeln!(out, "        for: {}", json_str(for_));
+        }
+        if let Some(severity) = &alert.severity {
+            out.push_str("        labels:\n");
+            let _ = writeln!(out, "          severity: {}", json_str(severity));
+        }
+        out.push_str("        annotations:\n");
+        let _ = writeln!(out, "          metric: {}", json_str(&metric.name));
+        if let Some(description) = &metric.description {
+            let _ = writeln!(out, "          summary: {}", json_str(description));
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::CatalogFormat;
+    use crate::{MacroArgs, Mod, Quantiles};
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    #[test]
+    fn expands_shorthands() {
+        let fn_ = parse_quote! { pub fn latency(route: &str) -> Histogram; };
+        assert_eq!(
+            expand_expr("p99 > 0.5", &fn_, None),
+            Ok(
+                "histogram_quantile(0.99, sum by (le, route) (rate(latency_bucket[5m]))) > 0.5"
+                    .to_owned()
+            )
+        );
+        let fn_ = parse_quote! { pub fn errors(route: &str, code: &str) -> Counter; };
+        assert_eq!(
+            expand_expr("sum by ({labels}) (rate({metric}[1m])) > 10", &fn_, None),
+            Ok("sum by (route, code) (rate(errors[1m])) > 10".to_owned())
+        );
+    }
+
+    #[test]
+    fn expression_must_refer_to_metric() {
+        let fn_ = parse_quote! { pub fn errors() -> Counter; };
+        assert!(expand_expr("up == 0", &fn_, None).is_err());
+        assert!(expand_expr("errors > 0", &fn_, None).is_ok());
+    }
+
+    #[test]
+    fn references_follow_renames() {
+        let mut fn_: ItemFn = parse_quote! { pub fn requests() -> Counter; };
+        fn_.metric_name = "requests_total".to_owned();
+        assert_eq!(
+            expand_expr("rate(requests[5m]) > 1", &fn_, None),
+            Ok("rate(requests_total[5m]) > 1".to_owned())
+        );
+        assert_eq!(
+            expand_expr("rate(requests_total[5m]) > 1", &fn_, None),
+            Ok("rate(requests_total[5m]) > 1".to_owned())
+        );
+    }
+
+    #[test]
+    fn string_literals_are_kept() {
+        let fn_ = parse_quote! { pub fn latency(route: &str) -> Histogram; };
+        assert_eq!(
+            expand_expr(r#"latency{route="latency"} > 1"#, &fn_, None),
+            Ok(r#"latency{route="latency"} > 1"#.to_owned())
+        );
+        assert_eq!(
+            expand_expr(r#"p99 > 1 unless on() up{handler='p99', path="\"p99\""}"#, &fn_, None),
+            Ok(r#"histogram_quantile(0.99, sum by (le, route) (rate(latency_bucket[5m]))) > 1 unless on() up{handler='p99', path="\"p99\""}"#.to_owned())
+        );
+        assert!(expand_expr(r#"up{job="latency"} == 0"#, &fn_, None).is_err());
+    }
+
+    #[test]
+    fn quantiles_only_on_histograms_and_summaries() {
+        let fn_ = parse_quote! { pub fn in_flight() -> Gauge; };
+        assert!(expand_expr("p99 > 1", &fn_, None).is_err());
+        let fn_ = parse_quote! { pub fn latency(route: &str) -> Summary; };
+        assert_eq!(
+            expand_expr("p99 > 1", &fn_, None),
+            Ok(r#"max by (route) (latency{quantile="0.99"}) > 1"#.to_owned())
+        );
+    }
+
+    #[test]
+    fn quantiles_are_formatted_like_exporters_do() {
+        let fn_ = parse_quote! { pub fn latency() -> Histogram; };
+        assert_eq!(
+            expand_expr("p50 > 1", &fn_, None),
+            Ok("histogram_quantile(0.5, sum by (le) (rate(latency_bucket[5m]))) > 1".to_owned())
+        );
+        let fn_ = parse_quote! { pub fn latency() -> Summary; };
+        assert_eq!(
+            expand_expr("p50 > 1 or p90 > 2", &fn_, Some(&[0.5, 0.9])),
+            Ok(r#"max by () (latency{quantile="0.5"}) > 1 or max by () (latency{quantile="0.9"}) > 2"#.to_owned())
+        );
+    }
+
+    #[test]
+    fn summary_quantiles_must_be_reported() {
+        let fn_ = parse_quote! { pub fn latency() -> Summary; };
+        assert!(expand_expr("p90 > 1", &fn_, Some(&Quantiles::DEFAULT)).is_ok());
+        assert_eq!(
+            expand_expr("p75 > 1", &fn_, Some(&Quantiles::DEFAULT)),
+            Err("`p75` isn't a quantile summaries report (0.5, 0.9, 0.99); add 0.75 to the `quantiles(...)` macro argument".to_owned())
+        );
+        assert!(expand_expr("p75 > 1", &fn_, Some(&[0.5, 0.75])).is_ok());
+        // Not checked until the macro arguments are known.
+        assert!(expand_expr("p75 > 1", &fn_, None).is_ok());
+    }
+
+    #[test]
+    fn rule_file() {
+        let mod_: Mod = parse_quote! {
+            mod app_metrics {
+                #[description = "Request latency"]
+                #[unit = metrics::Unit::Seconds]
+                #[alert(name = "HighLatency", expr = "p99 > 0.5", for = "5m", severity = "page")]
+                pub fn latency(route: &str) -> Histogram;
+
+                #[alert(name = "Restarting", expr = "increase({metric}[1h]) > 3")]
+                pub fn restarts() -> Counter;
+
+                pub fn in_flight() -> Gauge;
+            }
+        };
+        let catalog = Catalog::new(Some("my_service".to_owned()), &mod_);
+
+        let expected = r#"groups:
+  - name: "my_service.app_metrics"
+    rules:
+      - alert: "HighLatency"
+        expr: "histogram_quantile(0.99, sum by (le, route) (rate(latency_bucket[5m]))) > 0.5"
+        for: "5m"
+        labels:
+          severity: "page"
+        annotations:
+          metric: "latency"
+          summary: "Request latency"
+      - alert: "Restarting"
+        expr: "increase(restarts[1h]) > 3"
+        annotations:
+          metric: "restarts"
+"#;
+        assert_eq!(catalog.render(CatalogFormat::AlertRules), expected);
+    }
+
+    #[test]
+    fn alerts_follow_prometheus_conventions() {
+        let args: MacroArgs = parse_quote! { conventions = "prometheus" };
+        let mut mod_: Mod = parse_quote! {
+            mod app_metrics {
+                #[description = "requests"]
+                #[alert(name = "Busy", expr = "rate(requests[5m]) > 1")]
+                #[alert(name = "Idle", expr = "rate({metric}[5m]) == 0")]
+                pub fn requests() -> Counter;
+            }
+        };
+        mod_.apply_args(&args).unwrap();
+
+        let exprs: Vec<_> = mod_.fns[0]
+            .attrs
+            .alerts
+            .iter()
+            .map(|alert| alert.expanded_expr.as_str())
+            .collect();
+        assert_eq!(
+            exprs,
+            [
+                "rate(requests_total[5m]) > 1",
+                "rate(requests_total[5m]) == 0"
+            ]
+        );
+    }
+}
@@ -0,0 +1,586 @@
+//! Machine-readable catalog of the metrics declared in a `#[necessary_metrics]` module.
+//!
+//! The catalog is written at build time when the module opts in with
+//! `#[necessary_metrics(catalog)]` and the `NECESSARY_METRICS_CATALOG_DIR` environment variable
+//! is set. Its shape is versioned by [`SCHEMA_VERSION`]; bump it on any breaking change to the
+//! emitted fields.
+//!
+//! Besides JSON and YAML, the catalog can be rendered as a human-readable Markdown reference page,
+//! suitable for checking into the repository: the output only depends on the module's
+//! declarations, in declaration order.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use syn::{Expr, Lit};
+
+use crate::units::Unit;
+use crate::{FnAttrs, FnReturnTy, Mod};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Environment variable holding the directory catalogs are written to. Catalogs are only written
+/// when it is set, so regular builds have no side effects.
+pub const CATALOG_DIR_ENV_VAR: &str = "NECESSARY_METRICS_CATALOG_DIR";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalogFormat {
+    Json,
+    Yaml,
+    Markdown,
+    /// A Grafana dashboard, see [`crate::grafana`].
+    Grafana,
+    /// Prometheus alerting rules, see [`crate::alerts`].
+    AlertRules,
+}
+
+impl CatalogFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            CatalogFormat::Json => "json",
+            CatalogFormat::Yaml => "yaml",
+            CatalogFormat::Markdown => "md",
+            CatalogFormat::Grafana => "grafana.json",
+            CatalogFormat::AlertRules => "rules.yaml",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Catalog {
+    pub crate_name: Option<String>,
+    pub module: String,
+    pub metrics: Vec<CatalogMetric>,
+}
+
+#[derive(Debug)]
+pub struct CatalogMetric {
+    pub name: String,
+    pub kind: MetricKind,
+    pub unit: Option<&'static str>,
+    pub description: Option<String>,
+    pub labels: Vec<CatalogLabel>,
+    /// Rust docs. Only rendered in the Markdown reference.
+    pub docs: String,
+    /// Only rendered in alerting rules.
+    pub alerts: Vec<CatalogAlert>,
+}
+
+/// The kind of a metric, as the catalog names it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    Histogram,
+    Summary,
+    Info,
+    StateSet,
+}
+
+impl MetricKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MetricKind::Counter => "counter",
+            MetricKind::Gauge => "gauge",
+            MetricKind::Histogram => "histogram",
+            MetricKind::Summary => "summary",
+            MetricKind::Info => "info",
+            MetricKind::StateSet => "stateset",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CatalogAlert {
+    pub name: String,
+    /// PromQL, with shorthands expanded.
+    pub expr: String,
+    pub for_: Option<String>,
+    pub severity: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CatalogLabel {
+    pub key: String,
+    pub values: Option<Vec<String>>,
+}
+
+impl CatalogMetric {
+    /// The Rust docs as Markdown text, without the leading space doc comments carry after `///`.
+    pub fn docs_text(&self) -> String {
+        let lines: Vec<_> = self
+            .docs
+            .lines()
+            .map(|line| line.strip_prefix(' ').unwrap_or(line))
+            .collect();
+
+        lines.join("\n").trim().to_owned()
+    }
+}
+
+impl Catalog {
+    pub fn new(crate_name: Option<String>, mod_: &Mod) -> Self {
+        let metrics = mod_
+            .fns
+            .iter()
+            .map(|fn_| {
+                let name = fn_.metric_name.clone();
+                let alerts = fn_
+                    .attrs
+                    .alerts
+                    .iter()
+                    .map(|alert| CatalogAlert {
+                        name: alert.name.clone(),
+                        expr: alert.expanded_expr.clone(),
+                        for_: alert.for_.clone(),
+                        severity: alert.severity.clone(),
+                    })
+                    .collect();
+
+                CatalogMetric {
+                    name,
+                    kind: fn_.fn_return_ty.kind(),
+                    unit: fn_
+                        .attrs
+                        .unit
+                        .as_ref()
+                        .and_then(Unit::from_expr)
+                        .map(Unit::as_str),
+                    // Descriptions that aren't string literals can only be known at runtime.
+                    description: fn_.attrs.description.as_ref().and_then(|expr| match expr {
+                        Expr::Lit(expr) => match &expr.lit {
+                            Lit::Str(lit_str) => Some(lit_str.value()),
+                            _ => None,
+                        },
+                        _ => None,
+                    }),
+                    labels: fn_
+                        .args
+                        .iter()
+                        .map(|arg| CatalogLabel {
+                            key: arg.ident.to_string(),
+                            values: arg.values.clone(),
+                        })
+                        .chain(fn_.attrs.record_cancelled.map(|_| CatalogLabel {
+                            key: "outcome".to_owned(),
+                            values: Some(FnAttrs::OUTCOMES.map(str::to_owned).to_vec()),
+                        }))
+                        .chain(match fn_.fn_return_ty {
+                            // States are only known to the compiler.
+                            FnReturnTy::StateSet(_) => Some(CatalogLabel {
+                                key: fn_.metric_name.clone(),
+                                values: None,
+                            }),
+                            _ => None,
+                        })
+                        .collect(),
+                    docs: fn_.attrs.doc.clone(),
+                    alerts,
+                }
+            })
+            .collect();
+
+        Self {
+            crate_name,
+            module: mod_.ident.to_string(),
+            metrics,
+        }
+    }
+
+    pub fn render(&self, format: CatalogFormat) -> String {
+        match format {
+            CatalogFormat::Json => self.to_json(),
+            CatalogFormat::Yaml => self.to_yaml(),
+            CatalogFormat::Markdown => self.to_markdown(),
+            CatalogFormat::Grafana => crate::grafana::dashboard(self),
+            CatalogFormat::AlertRules => crate::alerts::rules(self),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push_str("{\n");
+        let _ = writeln!(out, "  \"schema_version\": {SCHEMA_VERSION},");
+        let _ = writeln!(
+            out,
+            "  \"crate\": {},",
+            json_opt_str(self.crate_name.as_deref())
+        );
+        let _ = writeln!(out, "  \"module\": {},", json_str(&self.module));
+        out.push_str("  \"metrics\": [");
+        for (i, metric) in self.metrics.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            out.push_str("    {\n");
+            let _ = writeln!(out, "      \"name\": {},", json_str(&metric.name));
+            let _ = writeln!(out, "      \"kind\": {},", json_str(metric.kind.as_str()));
+            let _ = writeln!(out, "      \"unit\": {},", json_opt_str(metric.unit));
+            let _ = writeln!(
+                out,
+                "      \"description\": {},",
+                json_opt_str(metric.description.as_deref())
+            );
+            out.push_str("      \"labels\": [");
+            for (j, label) in metric.labels.iter().enumerate() {
+                out.push_str(if j == 0 { "\n" } else { ",\n" });
+                let values = match &label.values {
+                    Some(values) => {
+                        let values: Vec<_> = values.iter().map(|v| json_str(v)).collect();
+                        format!("[{}]", values.join(", "))
+                    }
+                    None => "null".to_owned(),
+                };
+                let _ = write!(
+                    out,
+                    "        {{ \"key\": {}, \"values\": {} }}",
+                    json_str(&label.key),
+                    values
+                );
+            }
+            out.push_str(if metric.labels.is_empty() {
+                "]\n"
+            } else {
+                "\n      ]\n"
+            });
+            out.push_str("    }");
+        }
+        out.push_str(if self.metrics.is_empty() {
+            "]\n"
+        } else {
+            "\n  ]\n"
+        });
+        out.push_str("}\n");
+
+        out
+    }
+
+    /// Strings are always emitted double-quoted, with the same escaping as JSON, which is valid
+    /// YAML and sidesteps YAML's implicit typing of plain scalars.
+    fn to_yaml(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "schema_version: {SCHEMA_VERSION}");
+        let _ = writeln!(out, "crate: {}", json_opt_str(self.crate_name.as_deref()));
+        let _ = writeln!(out, "module: {}", json_str(&self.module));
+        if self.metrics.is_empty() {
+            out.push_str("metrics: []\n");
+        } else {
+            out.push_str("metrics:\n");
+        }
+        for metric in &self.metrics {
+            let _ = writeln!(out, "  - name: {}", json_str(&metric.name));
+            let _ = writeln!(out, "    kind: {}", json_str(metric.kind.as_str()));
+            let _ = writeln!(out, "    unit: {}", json_opt_str(metric.unit));
+            let _ = writeln!(
+                out,
+                "    description: {}",
+                json_opt_str(metric.description.as_deref())
+            );
+            if metric.labels.is_empty() {
+                out.push_str("    labels: []\n");
+            } else {
+                out.push_str("    labels:\n");
+            }
+            for label in &metric.labels {
+                let _ = writeln!(out, "      - key: {}", json_str(&label.key));
+                match &label.values {
+                    Some(values) => {
+                        let values: Vec<_> = values.iter().map(|v| json_str(v)).collect();
+                        let _ = writeln!(out, "        values: [{}]", values.join(", "));
+                    }
+                    None => out.push_str("        values: null\n"),
+                }
+            }
+        }
+
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# `{}` metrics reference\n", self.module);
+        out.push_str("<!-- Generated by `necessary-metrics`. Do not edit by hand. -->\n\n");
+        if let Some(crate_name) = &self.crate_name {
+            let _ = writeln!(out, "Metrics declared in crate `{crate_name}`.\n");
+        }
+
+        if self.metrics.is_empty() {
+            out.push_str("This module declares no metrics.\n");
+            return out;
+        }
+
+        out.push_str("| Metric | Type | Unit | Labels | Description |\n");
+        out.push_str("| --- | --- | --- | --- | --- |\n");
+        for metric in &self.metrics {
+            let labels: Vec<_> = metric
+                .labels
+                .iter()
+                .map(|label| format!("`{}`", label.key))
+                .collect();
+            let _ = writeln!(
+                out,
+                "| [`{name}`](#{name}) | {} | {} | {} | {} |",
+                metric.kind.as_str(),
+                metric.unit.unwrap_or("-"),
+                if labels.is_empty() {
+                    "-".to_owned()
+                } else {
+                    labels.join(", ")
+                },
+                metric
+                    .description
+                    .as_deref()
+                    .map(markdown_table_cell)
+                    .unwrap_or_else(|| "-".to_owned()),
+                name = metric.name,
+            );
+        }
+
+        for metric in &self.metrics {
+            let _ = writeln!(out, "\n## `{}`\n", metric.name);
+            let _ = writeln!(out, "- **Type:** {}", metric.kind.as_str());
+            if let Some(unit) = metric.unit {
+                let _ = writeln!(out, "- **Unit:** {unit}");
+            }
+            if let Some(description) = &metric.description {
+                let _ = writeln!(out, "- **Description:** {description}");
+            }
+            if metric.labels.is_empty() {
+                out.push_str("- **Labels:** none\n");
+            } else {
+                out.push_str("- **Labels:**\n");
+                for label in &metric.labels {
+                    match &label.values {
+                        Some(values) => {
+                            let values: Vec<_> = values.iter().map(|v| format!("`{v}`")).collect();
+                            let _ = writeln!(out, "  - `{}`: {}", label.key, values.join(", "));
+                        }
+                        None => {
+                            let _ = writeln!(out, "  - `{}`", label.key);
+                        }
+                    }
+                }
+            }
+
+            let docs = metric.docs_text();
+            if !docs.is_empty() {
+                let _ = writeln!(out, "\n{docs}");
+            }
+        }
+
+        out
+    }
+}
+
+fn markdown_table_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Writes the catalog for `mod_` to [`CATALOG_DIR_ENV_VAR`], if set. The file is named after the
+/// crate being compiled and the module's path in it, e.g. `my_service.net.app_metrics.json`, so
+/// that modules of the same name don't overwrite each other's catalog. The parent modules are
+/// read off `source_file`, the file the module is declared in.
+pub fn write(mod_: &Mod, format: CatalogFormat, source_file: Option<&Path>) -> syn::Result<()> {
+    let Some(dir) = std::env::var_os(CATALOG_DIR_ENV_VAR) else {
+        return Ok(());
+    };
+    let crate_name = std::env::var("CARGO_CRATE_NAME").ok();
+    let catalog = Catalog::new(crate_name.clone(), mod_);
+
+    let mut file_name = String::new();
+    if let Some(crate_name) = &crate_name {
+        file_name.push_str(crate_name);
+        file_name.push('.');
+    }
+    if let Some(source_file) = source_file {
+        // Relative paths are relative to the compiler's working directory, like the workspace's.
+        let source_file = std::env::current_dir()
+            .map(|dir| dir.join(source_file))
+            .unwrap_or_else(|_| source_file.to_owned());
+        let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from);
+        let source_file = manifest_dir
+            .and_then(|dir| source_file.strip_prefix(dir).ok())
+            .unwrap_or(&source_file);
+        for parent in parent_modules(source_file) {
+            file_name.push_str(&parent);
+            file_name.push('.');
+        }
+    }
+    let _ = write!(file_name, "{}.{}", mod_.ident, format.extension());
+    let dir = PathBuf::from(dir);
+
+    std::fs::create_dir_all(&dir)
+        .and_then(|()| std::fs::write(dir.join(&file_name), catalog.render(format)))
+        .map_err(|e| {
+            syn::Error::new(
+                mod_.ident.span(),
+                format!("Failed to write metrics catalog `{file_name}`: {e}"),
+            )
+        })
+}
+
+/// The modules a file declares its items in, from its path relative to the crate's manifest
+/// directory: `src/net/mod.rs` and `src/net.rs` are `net`, `src/lib.rs` the crate root. Modules
+/// declared inline are not in the path, so inline siblings of the same name share their parents.
+fn parent_modules(source_file: &Path) -> Vec<String> {
+    let mut modules: Vec<_> = source_file
+        .with_extension("")
+        .iter()
+        .map(|component| component.to_string_lossy().into_owned())
+        .collect();
+    if let Some("src" | "tests" | "examples" | "benches") = modules.first().map(String::as_str) {
+        modules.remove(0);
+    }
+    if let Some("lib" | "main" | "mod") = modules.last().map(String::as_str) {
+        modules.pop();
+    }
+
+    modules
+}
+
+pub fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+
+    out
+}
+
+fn json_opt_str(s: Option<&str>) -> String {
+    s.map(json_str).unwrap_or_else(|| "null".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    fn catalog() -> Catalog {
+        let mod_: Mod = parse_quote! {
+            mod app_metrics {
+                /// How long a task takes.
+                ///
+                /// Measured from scheduling to completion.
+                #[description = "task \"latency\""]
+                #[unit = metrics::Unit::Milliseconds]
+                pub fn task_latency(#[values("build", "test")] task_kind: TaskKind, host: &str) -> Histogram;
+
+                pub fn restarts() -> Counter;
+            }
+        };
+
+        Catalog::new(Some("my_service".to_owned()), &mod_)
+    }
+
+    #[test]
+    fn parent_modules_follow_the_source_file() {
+        let parents = |file: &str| parent_modules(Path::new(file));
+        assert_eq!(parents("src/lib.rs"), Vec::<String>::new());
+        assert_eq!(parents("src/main.rs"), Vec::<String>::new());
+        assert_eq!(parents("src/net.rs"), ["net"]);
+        assert_eq!(parents("src/net/mod.rs"), ["net"]);
+        assert_eq!(parents("src/net/http.rs"), ["net", "http"]);
+        assert_eq!(parents("src/bin/server.rs"), ["bin", "server"]);
+        assert_eq!(parents("tests/items.rs"), ["items"]);
+    }
+
+    #[test]
+    fn json() {
+        let expected = r#"{
+  "schema_version": 1,
+  "crate": "my_service",
+  "module": "app_metrics",
+  "metrics": [
+    {
+      "name": "task_latency",
+      "kind": "histogram",
+      "unit": "milliseconds",
+      "description": "task \"latency\"",
+      "labels": [
+        { "key": "task_kind", "values": ["build", "test"] },
+        { "key": "host", "values": null }
+      ]
+    },
+    {
+      "name": "restarts",
+      "kind": "counter",
+      "unit": null,
+      "description": null,
+      "labels": []
+    }
+  ]
+}
+"#;
+        assert_eq!(catalog().render(CatalogFormat::Json), expected);
+    }
+
+    #[test]
+    fn yaml() {
+        let expected = r#"schema_version: 1
+crate: "my_service"
+module: "app_metrics"
+metrics:
+  - name: "task_latency"
+    kind: "histogram"
+    unit: "milliseconds"
+    description: "task \"latency\""
+    labels:
+      - key: "task_kind"
+        values: ["build", "test"]
+      - key: "host"
+        values: null
+  - name: "restarts"
+    kind: "counter"
+    unit: null
+    description: null
+    labels: []
+"#;
+        assert_eq!(catalog().render(CatalogFormat::Yaml), expected);
+    }
+
+    #[test]
+    fn markdown() {
+        let expected = r#"# `app_metrics` metrics reference
+
+<!-- Generated by `necessary-metrics`. Do not edit by hand. -->
+
+Metrics declared in crate `my_service`.
+
+| Metric | Type | Unit | Labels | Description |
+| --- | --- | --- | --- | --- |
+| [`task_latency`](#task_latency) | histogram | milliseconds | `task_kind`, `host` | task "latency" |
+| [`restarts`](#restarts) | counter | - | - | - |
+
+## `task_latency`
+
+- **Type:** histogram
+- **Unit:** milliseconds
+- **Description:** task "latency"
+- **Labels:**
+  - `task_kind`: `build`, `test`
+  - `host`
+
+How long a task takes.
+
+Measured from scheduling to completion.
+
+## `restarts`
+
+- **Type:** counter
+- **Labels:** none
+"#;
+        assert_eq!(catalog().render(CatalogFormat::Markdown), expected);
+    }
+}
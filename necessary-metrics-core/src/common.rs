@@ -0,0 +1,31 @@
+use syn::spanned::Spanned;
+
+pub(crate) fn error<T>(spanned: &impl Spanned, msg: &'static str) -> syn::Result<T> {
+    Err(syn::Error::new(spanned.span(), msg))
+}
+
+/// Collects errors to report them all at once, combined with [`syn::Error::combine`].
+#[derive(Default)]
+pub(crate) struct Errors(Option<syn::Error>);
+
+impl Errors {
+    pub(crate) fn push(&mut self, error: syn::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    /// The value of `result`, collecting its error otherwise.
+    pub(crate) fn ok<T>(&mut self, result: syn::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
+
+    pub(crate) fn into_error(self) -> Option<syn::Error> {
+        self.0
+    }
+
+    pub(crate) fn finish(self) -> syn::Result<()> {
+        self.0.map_or(Ok(()), Err)
+    }
+}
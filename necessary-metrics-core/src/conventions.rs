@@ -0,0 +1,228 @@
+//! Naming conventions a `#[necessary_metrics(conventions = "...")]` module opts into.
+//!
+//! Both profiles want snake_case names, base units, and no suffixes clashing with the series
+//! histograms and summaries are exported as. On top of that, Prometheus counters end in `_total`,
+//! which is appended when missing, while OpenTelemetry keeps units and `_total` out of names,
+//! leaving them to exporters.
+
+use crate::lints::Warning;
+use crate::units::Unit;
+use crate::{FnReturnTy, ItemFn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conventions {
+    /// <https://prometheus.io/docs/practices/naming/>
+    Prometheus,
+    /// <https://opentelemetry.io/docs/specs/semconv/general/metrics/>
+    OpenTelemetry,
+}
+
+/// Suffixes of the series histograms and summaries are exported as.
+const RESERVED_SUFFIXES: [&str; 3] = ["_bucket", "_count", "_sum"];
+
+impl Conventions {
+    fn name(self) -> &'static str {
+        match self {
+            Conventions::Prometheus => "Prometheus",
+            Conventions::OpenTelemetry => "OpenTelemetry",
+        }
+    }
+
+    /// Amends the metric name where these conventions say how: Prometheus counters get `_total`.
+    pub(crate) fn apply(self, fn_: &mut ItemFn) {
+        if let (Conventions::Prometheus, FnReturnTy::Counter) = (self, &fn_.fn_return_ty) {
+            if !fn_.metric_name.ends_with("_total") {
+                fn_.metric_name.push_str("_total");
+            }
+        }
+    }
+
+    /// Checks the metric against these conventions, once [`Conventions::apply`] amended it.
+    pub(crate) fn check(self, fn_: &ItemFn, warnings: &mut Vec<Warning>) {
+        let conventions = self.name();
+        let name = &fn_.metric_name;
+        let mut warn = |message: String| warnings.push(Warning::new(fn_.ident.span(), message));
+
+        if !is_snake_case(name) {
+            warn(format!(
+                "{conventions} conventions want snake_case metric names; rename `{name}` to `{}`",
+                to_snake_case(name),
+            ));
+        }
+        if !matches!(
+            fn_.fn_return_ty,
+            FnReturnTy::Histogram | FnReturnTy::Summary
+        ) {
+            if let Some(suffix) = RESERVED_SUFFIXES.iter().find(|s| name.ends_with(*s)) {
+                warn(format!(
+                    "`{suffix}` is reserved for the series of histograms and summaries; rename `{name}` to `{}`",
+                    &name[..name.len() - suffix.len()],
+                ));
+            }
+        }
+        if let Some(unit) = fn_.attrs.unit.as_ref().and_then(Unit::from_expr) {
+            let base_unit = if unit.per_second().is_some() {
+                Some(Unit::Seconds)
+            } else if unit.bytes().is_some() {
+                Some(Unit::Bytes)
+            } else {
+                None
+            };
+            if let Some(base_unit) = base_unit.filter(|base_unit| *base_unit != unit) {
+                warn(format!(
+                    "{conventions} conventions want base units; record in {} rather than {}",
+                    base_unit.as_str(),
+                    unit.as_str(),
+                ));
+            }
+        }
+
+        let is_counter = matches!(fn_.fn_return_ty, FnReturnTy::Counter);
+        match self {
+            Conventions::Prometheus => {
+                if let (false, Some(stem)) = (is_counter, name.strip_suffix("_total")) {
+                    warn(format!(
+                        "`_total` is reserved for counters; rename `{name}` to `{stem}`"
+                    ));
+                }
+            }
+            Conventions::OpenTelemetry => {
+                if let Some(stem) = name.strip_suffix("_total") {
+                    warn(format!(
+                        "OpenTelemetry conventions leave `_total` to exporters; rename `{name}` to `{stem}`"
+                    ));
+                }
+                if let Some(unit) = Unit::from_name_suffix(name) {
+                    warn(format!(
+                        "OpenTelemetry conventions keep units out of metric names; rename `{name}` and set `#[unit = {}]` instead",
+                        unit.as_str(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn is_snake_case(name: &str) -> bool {
+    name.chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && !name.starts_with('_')
+        && !name.ends_with('_')
+        && !name.contains("__")
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        let boundary = c == '_' || (c.is_ascii_uppercase() && prev_lower);
+        if boundary && !snake.is_empty() && !snake.ends_with('_') {
+            snake.push('_');
+        }
+        if c != '_' {
+            snake.push(c.to_ascii_lowercase());
+        }
+        prev_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+    }
+
+    snake.trim_end_matches('_').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    /// The amended metric name, and the messages of the warnings.
+    fn apply(conventions: Conventions, mut fn_: ItemFn) -> (String, Vec<String>) {
+        let mut warnings = Vec::new();
+        conventions.apply(&mut fn_);
+        conventions.check(&fn_, &mut warnings);
+
+        (
+            fn_.metric_name,
+            warnings.into_iter().map(|w| w.message).collect(),
+        )
+    }
+
+    #[test]
+    fn prometheus_counters_end_in_total() {
+        let fn_ = parse_quote! { pub fn requests() -> Counter; };
+        assert_eq!(
+            apply(Conventions::Prometheus, fn_),
+            ("requests_total".to_owned(), vec![])
+        );
+
+        let fn_ = parse_quote! { pub fn requests_total() -> Counter; };
+        assert_eq!(
+            apply(Conventions::Prometheus, fn_),
+            ("requests_total".to_owned(), vec![])
+        );
+
+        let fn_ = parse_quote! { pub fn queue_total() -> Gauge; };
+        assert_eq!(
+            apply(Conventions::Prometheus, fn_).1,
+            ["`_total` is reserved for counters; rename `queue_total` to `queue`"]
+        );
+    }
+
+    #[test]
+    fn reserved_suffixes() {
+        let fn_ = parse_quote! { pub fn queue_count() -> Gauge; };
+        assert_eq!(
+            apply(Conventions::Prometheus, fn_).1,
+            ["`_count` is reserved for the series of histograms and summaries; rename `queue_count` to `queue`"]
+        );
+
+        let fn_ = parse_quote! { pub fn latency_sum() -> Histogram; };
+        assert!(apply(Conventions::OpenTelemetry, fn_).1.is_empty());
+    }
+
+    #[test]
+    fn base_units() {
+        let fn_ = parse_quote! {
+            #[description = "latency"]
+            #[unit = milliseconds]
+            pub fn latency() -> Histogram;
+        };
+        assert_eq!(
+            apply(Conventions::OpenTelemetry, fn_).1,
+            ["OpenTelemetry conventions want base units; record in seconds rather than milliseconds"]
+        );
+    }
+
+    #[test]
+    fn snake_case() {
+        let fn_ = parse_quote! { pub fn requestLatency() -> Histogram; };
+        assert_eq!(
+            apply(Conventions::Prometheus, fn_).1,
+            ["Prometheus conventions want snake_case metric names; rename `requestLatency` to `request_latency`"]
+        );
+        assert_eq!(to_snake_case("HTTP__Requests_"), "http_requests");
+    }
+
+    #[test]
+    fn open_telemetry_keeps_suffixes_out_of_names() {
+        let fn_ = parse_quote! { pub fn requests_total() -> Counter; };
+        assert_eq!(
+            apply(Conventions::OpenTelemetry, fn_).1,
+            ["OpenTelemetry conventions leave `_total` to exporters; rename `requests_total` to `requests`"]
+        );
+
+        let fn_ = parse_quote! {
+            #[description = "latency"]
+            #[unit = seconds]
+            pub fn latency_seconds() -> Histogram;
+        };
+        assert_eq!(
+            apply(Conventions::OpenTelemetry, fn_).1,
+            ["OpenTelemetry conventions keep units out of metric names; rename `latency_seconds` and set `#[unit = seconds]` instead"]
+        );
+
+        let fn_ = parse_quote! { pub fn requests() -> Counter; };
+        assert_eq!(
+            apply(Conventions::OpenTelemetry, fn_),
+            ("requests".to_owned(), vec![])
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! Grafana dashboard generated from a catalog: one panel per metric, chosen by kind.
+//!
+//! - Counters are plotted as a per-second `rate()`.
+//! - Gauges are shown as their last value.
+//! - Histograms are shown as a heatmap of their buckets, per label set.
+//! - Summaries are plotted per reported quantile.
+//! - Info metrics are listed in a table of their current label values.
+//! - State sets are shown as a timeline of their current state.
+//!
+//! Counters, gauges and histogram buckets are summed by their declared labels, so there's one
+//! series per label set.
+//! Panels are titled from the metric description, falling back to its name.
+
+use std::fmt::Write;
+
+use crate::catalog::{json_str, Catalog, CatalogMetric, MetricKind};
+
+/// Panels are laid out in a grid two panels wide.
+const PANEL_WIDTH: u32 = 12;
+const PANEL_HEIGHT: u32 = 8;
+
+/// Minimal JSON document model, so the dashboard's nesting reads like the output.
+enum Json {
+    Bool(bool),
+    Num(u32),
+    Str(String),
+    Arr(Vec<Json>),
+    Obj(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn str(s: impl Into<String>) -> Self {
+        Json::Str(s.into())
+    }
+
+    fn write(&self, out: &mut String, indent: usize) {
+        match self {
+            Json::Bool(b) => {
+                let _ = write!(out, "{b}");
+            }
+            Json::Num(n) => {
+                let _ = write!(out, "{n}");
+            }
+            Json::Str(s) => out.push_str(&json_str(s)),
+            Json::Arr(items) if items.is_empty() => out.push_str("[]"),
+            Json::Arr(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(",\n");
+                    }
+                    push_indent(out, indent + 1);
+                    item.write(out, indent + 1);
+                }
+                out.push('\n');
+                push_indent(out, indent);
+                out.push(']');
+            }
+            Json::Obj(fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Obj(fields) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push_str(",\n");
+                    }
+                    push_indent(out, indent + 1);
+                    let _ = write!(out, "{}: ", json_str(key));
+                    value.write(out, indent + 1);
+                }
+                out.push('\n');
+                push_indent(out, indent);
+                out.push('}');
+            }
+        }
+    }
+}
+
+fn push_indent(out: &mut String, indent: usize) {
+    for _ in 0..indent {
+        out.push_str("  ");
+    }
+}
+
+pub(crate) fn dashboard(catalog: &Catalog) -> String {
+    let title = match &catalog.crate_name {
+        Some(crate_name) => format!("{crate_name} / {}", catalog.module),
+        None => catalog.module.clone(),
+    };
+    // Grafana caps UIDs at 40 characters.
+    let uid: String = title
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .take(40)
+        .collect();
+
+    let panels = catalog
+        .metrics
+        .iter()
+        .enumerate()
+        .map(|(i, metric)| panel(i as u32, metric))
+        .collect();
+
+    let dashboard = Json::Obj(vec![
+        ("uid", Json::Str(uid)),
+        ("title", Json::Str(title)),
+        ("tags", Json::Arr(vec![Json::str("necessary-metrics")])),
+        ("editable", Json::Bool(true)),
+        ("schemaVersion", Json::Num(39)),
+        (
+            "time",
+            Json::Obj(vec![
+                ("from", Json::str("now-6h")),
+                ("to", Json::str("now")),
+            ]),
+        ),
+        (
+            "templating",
+            Json::Obj(vec![(
+                "list",
+                Json::Arr(vec![Json::Obj(vec![
+                    ("name", Json::str("datasource")),
+                    ("label", Json::str("Data source")),
+                    ("type", Json::str("datasource")),
+                    ("query", Json::str("prometheus")),
+                ])]),
+            )]),
+        ),
+        ("panels", Json::Arr(panels)),
+    ]);
+
+    let mut out = String::new();
+    dashboard.write(&mut out, 0);
+    out.push('\n');
+
+    out
+}
+
+fn panel(idx: u32, metric: &CatalogMetric) -> Json {
+    let name = &metric.name;
+    let labels: Vec<_> = metric.labels.iter().map(|l| l.key.as_str()).collect();
+    let by = labels.join(", ");
+    let legend = if labels.is_empty() {
+        name.clone()
+    } else {
+        labels
+            .iter()
+            .map(|label| format!("{{{{{label}}}}}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let (panel_type, expr, legend, unit, format, options) = match metric.kind {
+        MetricKind::Counter => (
+            "timeseries",
+            format!("sum by ({by}) (rate({name}[$__rate_interval]))"),
+            legend,
+            counter_unit(metric.unit),
+            "time_series",
+            Json::Obj(vec![]),
+        ),
+        MetricKind::Gauge => (
+            "stat",
+            format!("sum by ({by}) ({name})"),
+            legend,
+            grafana_unit(metric.unit),
+            "time_series",
+            Json::Obj(vec![(
+                "reduceOptions",
+                Json::Obj(vec![
+                    ("calcs", Json::Arr(vec![Json::str("lastNotNull")])),
+                    ("fields", Json::str("")),
+                    ("values", Json::Bool(false)),
+                ]),
+            )]),
+        ),
+        MetricKind::Summary => (
+            "timeseries",
+            if by.is_empty() {
+                format!("max by (quantile) ({name})")
+            } else {
+                format!("max by (quantile, {by}) ({name})")
+            },
+            if labels.is_empty() {
+                "p{{quantile}}".to_owned()
+            } else {
+                format!("p{{{{quantile}}}} {legend}")
+            },
+            grafana_unit(metric.unit),
+            "time_series",
+            Json::Obj(vec![]),
+        ),
+        MetricKind::Info => (
+            "table",
+            format!("max by ({by}) ({name}) == 1"),
+            legend,
+            "short",
+            "table",
+            Json::Obj(vec![]),
+        ),
+        MetricKind::StateSet => (
+            "state-timeline",
+            format!("{name} == 1"),
+            if labels.len() > 1 {
+                legend
+            } else {
+                name.clone()
+            },
+            "short",
+            "time_series",
+            Json::Obj(vec![]),
+        ),
+        MetricKind::Histogram => (
+            "heatmap",
+            if by.is_empty() {
+                format!("sum by (le) (rate({name}_bucket[$__rate_interval]))")
+            } else {
+                format!("sum by (le, {by}) (rate({name}_bucket[$__rate_interval]))")
+            },
+            if labels.is_empty() {
+                "{{le}}".to_owned()
+            } else {
+                format!("{{{{le}}}} {legend}")
+            },
+            grafana_unit(metric.unit),
+            "heatmap",
+            Json::Obj(vec![(
+                "calculate",
+                // Buckets come precomputed from Prometheus.
+                Json::Bool(false),
+            )]),
+        ),
+    };
+    Json::Obj(vec![
+        ("id", Json::Num(idx + 1)),
+        ("type", Json::str(panel_type)),
+        (
+            "title",
+            Json::Str(metric.description.clone().unwrap_or_else(|| name.clone())),
+        ),
+        ("description", Json::Str(metric.docs_text())),
+        (
+            "gridPos",
+            Json::Obj(vec![
+                ("x", Json::Num((idx % 2) * PANEL_WIDTH)),
+                ("y", Json::Num((idx / 2) * PANEL_HEIGHT)),
+                ("w", Json::Num(PANEL_WIDTH)),
+                ("h", Json::Num(PANEL_HEIGHT)),
+            ]),
+        ),
+        ("datasource", datasource()),
+        (
+            "fieldConfig",
+            Json::Obj(vec![
+                ("defaults", Json::Obj(vec![("unit", Json::str(unit))])),
+                ("overrides", Json::Arr(vec![])),
+            ]),
+        ),
+        ("options", options),
+        (
+            "targets",
+            Json::Arr(vec![Json::Obj(vec![
+                ("refId", Json::str("A")),
+                ("datasource", datasource()),
+                ("expr", Json::Str(expr)),
+                ("legendFormat", Json::Str(legend)),
+                ("format", Json::str(format)),
+            ])]),
+        ),
+    ])
+}
+
+fn datasource() -> Json {
+    Json::Obj(vec![
+        ("type", Json::str("prometheus")),
+        ("uid", Json::str("${datasource}")),
+    ])
+}
+
+/// Maps `metrics::Unit` names to Grafana unit identifiers.
+fn grafana_unit(unit: Option<&str>) -> &'static str {
+    match unit {
+        Some("percent") => "percent",
+        Some("seconds") => "s",
+        Some("milliseconds") => "ms",
+        Some("microseconds") => "µs",
+        Some("nanoseconds") => "ns",
+        Some("tebibytes") => "tbytes",
+        Some("gigibytes") => "gbytes",
+        Some("mebibytes") => "mbytes",
+        Some("kibibytes") => "kbytes",
+        Some("bytes") => "bytes",
+        Some("terabits_per_second") => "Tbits",
+        Some("gigabits_per_second") => "Gbits",
+        Some("megabits_per_second") => "Mbits",
+        Some("kilobits_per_second") => "Kbits",
+        Some("bits_per_second") => "bps",
+        Some("count_per_second") => "cps",
+        _ => "short",
+    }
+}
+
+/// The unit of a counter's rate: bytes become bytes per second, anything else a count per second.
+fn counter_unit(unit: Option<&str>) -> &'static str {
+    match unit {
+        Some("bytes" | "kibibytes" | "mebibytes" | "gigibytes" | "tebibytes") => "Bps",
+        _ => "cps",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mod;
+    use syn::parse_quote;
+
+    fn dashboard_for(mod_: Mod) -> String {
+        dashboard(&Catalog::new(Some("my_service".to_owned()), &mod_))
+    }
+
+    #[test]
+    fn panel_per_metric_kind() {
+        let dashboard = dashboard_for(parse_quote! {
+            mod app_metrics {
+                #[description = "Requests served"]
+                pub fn requests(route: &str, status: &str) -> Counter;
+
+                pub fn in_flight() -> Gauge;
+
+                #[description = "Request latency"]
+                #[unit = metrics::Unit::Seconds]
+                pub fn latency(route: &str) -> Histogram;
+
+                pub fn queue_time(queue: &str) -> Summary;
+
+                pub fn build_info(version: &str) -> Info;
+            }
+        });
+
+        assert!(dashboard.contains(r#""uid": "my-service---app-metrics""#));
+        assert!(dashboard.contains(r#""title": "Requests served""#));
+        assert!(dashboard
+            .contains(r#""expr": "sum by (route, status) (rate(requests[$__rate_interval]))""#));
+        assert!(dashboard.contains(r#""legendFormat": "{{route}} {{status}}""#));
+        assert!(dashboard.contains(r#""title": "in_flight""#));
+        assert!(dashboard.contains(r#""expr": "sum by () (in_flight)""#));
+        assert!(dashboard.contains(r#""type": "heatmap""#));
+        assert!(dashboard
+            .contains(r#""expr": "sum by (le, route) (rate(latency_bucket[$__rate_interval]))""#));
+        assert!(dashboard.contains(r#""legendFormat": "{{le}} {{route}}""#));
+        assert!(dashboard.contains(r#""unit": "s""#));
+        assert!(dashboard.contains(r#""expr": "max by (quantile, queue) (queue_time)""#));
+        assert!(dashboard.contains(r#""legendFormat": "p{{quantile}} {{queue}}""#));
+        assert!(dashboard.contains(r#""expr": "max by (version) (build_info) == 1""#));
+    }
+
+    #[test]
+    fn empty_module() {
+        let expected = r#"{
+  "uid": "my-service---metrics",
+  "title": "my_service / metrics",
+  "tags": [
+    "necessary-metrics"
+  ],
+  "editable": true,
+  "schemaVersion": 39,
+  "time": {
+    "from": "now-6h",
+    "to": "now"
+  },
+  "templating": {
+    "list": [
+      {
+        "name": "datasource",
+        "label": "Data source",
+        "type": "datasource",
+        "query": "prometheus"
+      }
+    ]
+  },
+  "panels": []
+}
+"#;
+        assert_eq!(dashboard_for(parse_quote! { mod metrics {} }), expected);
+    }
+}
@@ -0,0 +1,397 @@
+//! Parsing and metadata shared by the [`necessary-metrics`] attribute macro and the
+//! `necessary-metrics-cli` tool, so that both read metric declarations the same way.
+//!
+//! This crate is an implementation detail; its API is not stable.
+//!
+//! [`necessary-metrics`]: https://docs.rs/necessary-metrics
+use crate::common::Errors;
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::parse_quote;
+use syn::spanned::Spanned;
+use syn::{punctuated::Punctuated, Attribute, Expr, Ident, Item, LitStr, Token, Type, Visibility};
+
+pub mod alerts;
+pub mod catalog;
+mod common;
+pub mod conventions;
+mod grafana;
+pub mod lints;
+mod parsing;
+pub mod units;
+
+/// Arguments passed to the attribute itself, e.g. `#[necessary_metrics(catalog = "yaml")]`.
+#[derive(Debug, Default)]
+pub struct MacroArgs {
+    /// Formats of the metrics catalog to write at build time, if requested.
+    pub catalog: Vec<catalog::CatalogFormat>,
+    /// Declare a standard build-info metric, from a `build_info` argument.
+    pub build_info: Option<BuildInfo>,
+    /// Append the canonical suffix of their unit to metric names, from a `unit_suffixes`
+    /// argument.
+    pub unit_suffixes: bool,
+    /// Naming conventions to check metrics against, from a `conventions = "..."` argument.
+    pub conventions: Option<conventions::Conventions>,
+    /// Report [`lints`] as errors rather than warnings, from a `deny_warnings` argument.
+    pub deny_warnings: bool,
+    /// A path the `metrics` crate is also reachable through, like a re-export, from a
+    /// `metrics_alias = "..."` argument: return types can be qualified with it as well.
+    pub metrics_alias: Option<syn::Path>,
+    /// Quantiles every summary in the module reports, from a `quantiles(...)` argument.
+    pub quantiles: Option<Quantiles>,
+    /// The window every summary in the module is computed over, from a `window(...)` argument.
+    pub window: Option<SummaryWindow>,
+    /// Generate a function applying histogram buckets and summary settings to this exporter,
+    /// from an `exporter = "..."` argument.
+    pub exporter: Option<Exporter>,
+}
+
+/// Exporters the macro can generate configuration code for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exporter {
+    /// `metrics-exporter-prometheus`, configured through its `PrometheusBuilder`.
+    Prometheus,
+}
+
+/// A `build_info(label = "ENV_VAR", ...)` macro argument: declares an [`FnReturnTy::Info`] metric
+/// named `build_info` labelled with the crate version and the given compile-time environment
+/// variables.
+#[derive(Debug, Clone)]
+pub struct BuildInfo {
+    pub env_labels: Vec<(Ident, LitStr)>,
+    pub span: Span,
+}
+
+/// A module annotated with `#[necessary_metrics]`.
+#[derive(Debug)]
+pub struct Mod {
+    pub attrs: Vec<Attribute>,
+    /// Inner attributes, like `#![allow(...)]`, kept at the top of the module.
+    pub inner_attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub mod_token: Token![mod],
+    pub ident: Ident,
+    pub fns: Vec<ItemFn>,
+    /// Everything but the metric declarations, like `use` statements, label enums or tests,
+    /// passed through untouched.
+    pub items: Vec<Item>,
+}
+
+/// A module parsed as far as possible, see [`Mod::parse_partial`].
+#[derive(Debug)]
+pub struct PartialMod {
+    /// The module, without the functions that failed to parse.
+    pub mod_: Mod,
+    /// The errors of the functions that failed to parse, combined.
+    pub errors: Option<syn::Error>,
+    /// The signatures of the functions that failed to parse, where they read as plain bodyless
+    /// functions, so that stand-ins can be generated for them.
+    pub unparsed_fns: Vec<syn::ForeignItemFn>,
+}
+
+/// A bodyless function declaring a metric.
+#[derive(Debug)]
+pub struct ItemFn {
+    pub attrs: FnAttrs,
+    pub vis: Visibility,
+    pub fn_token: Token![fn],
+    pub ident: Ident,
+    /// The name the metric is emitted under: the function name, unless macro arguments like
+    /// `unit_suffixes` amend it.
+    pub metric_name: String,
+    pub args: Punctuated<FnArg, Token![,]>,
+    pub arrow_token: Token![->],
+    pub fn_return_ty: FnReturnTy,
+    /// The path the return type is qualified with, like `metrics` in `metrics::Counter`, checked
+    /// by [`Mod::apply_args`] since the `metrics` crate may be aliased.
+    pub return_ty_qualifier: Option<syn::Path>,
+}
+
+#[derive(Debug)]
+pub enum FnReturnTy {
+    Counter,
+    Gauge,
+    Histogram,
+    /// Emitted as a histogram, which exporters like `metrics-exporter-prometheus` render as a
+    /// summary with client-side quantiles, configured for the whole module by
+    /// [`MacroArgs::quantiles`] and [`MacroArgs::window`].
+    Summary,
+    /// A gauge set to 1 for the current label values only, e.g. `build_info{version="1.2.3"} 1`.
+    Info,
+    /// An OpenMetrics state set: a gauge per state of the given enum, labelled with the metric
+    /// name, set to 1 for the current state and 0 for the others.
+    StateSet(Box<Type>),
+}
+
+#[derive(Debug)]
+pub struct FnAttrs {
+    pub cfg: Vec<Attribute>,
+    pub doc: String,
+    pub description: Option<Expr>,
+    pub unit: Option<Expr>,
+    pub alerts: Vec<Alert>,
+    pub buckets: Option<Buckets>,
+    /// Set by `#[record_cancelled]`: futures timed with `<metric>_time` also record when dropped
+    /// before completing, with an `outcome` label telling the two apart. Every other recording is
+    /// labelled as completed.
+    pub record_cancelled: Option<Span>,
+    /// Set by `#[typed]`: the metric function returns a generated wrapper whose methods take
+    /// values typed after the metric's unit, like `Duration` for time units.
+    pub typed: Option<Span>,
+    /// Only set on the metric declared by the `build_info` macro argument.
+    pub build_info: Option<BuildInfo>,
+}
+
+/// Histogram bucket boundaries, from a `#[buckets(...)]` attribute. `exponential(...)` and
+/// `linear(...)` are expanded when parsing, so `bounds` are always explicit, finite and strictly
+/// increasing.
+///
+/// The recorder has no per-histogram settings, so they are passed to the exporter instead: by the
+/// generated `configure_exporter()` with [`MacroArgs::exporter`], or by hand through the generated
+/// `<metric>_buckets()` and `all_histogram_buckets()`.
+#[derive(Debug)]
+pub struct Buckets {
+    pub bounds: Vec<f64>,
+    pub span: Span,
+}
+
+/// Quantiles summaries report, from a `quantiles(...)` macro argument. Always in `[0, 1]` and
+/// strictly increasing.
+///
+/// Exporters like `metrics-exporter-prometheus` compute the quantiles of every summary alike, so
+/// they are set for the module rather than per metric, and applied to the exporter by the
+/// generated `configure_exporter()` with [`MacroArgs::exporter`].
+#[derive(Debug)]
+pub struct Quantiles {
+    pub quantiles: Vec<f64>,
+    pub span: Span,
+}
+
+impl Quantiles {
+    pub const DEFAULT: [f64; 3] = [0.5, 0.9, 0.99];
+}
+
+/// The sliding window summaries compute their quantiles over, from a
+/// `window(max_age = "10m", age_buckets = 5)` macro argument: observations older than `max_age`
+/// are discarded, `age_buckets` at a time. Set for the module, like [`Quantiles`].
+#[derive(Debug)]
+pub struct SummaryWindow {
+    pub max_age_secs: u64,
+    pub age_buckets: u32,
+    pub span: Span,
+}
+
+impl SummaryWindow {
+    /// Same defaults as the official Prometheus clients.
+    pub const DEFAULT_MAX_AGE_SECS: u64 = 600;
+    pub const DEFAULT_AGE_BUCKETS: u32 = 5;
+}
+
+/// An alerting rule on the metric, from an `#[alert(...)]` attribute.
+#[derive(Debug)]
+pub struct Alert {
+    pub name: String,
+    /// PromQL, with the shorthands described in [`alerts`].
+    pub expr: LitStr,
+    /// `expr` with its shorthands expanded against the final metric name, see
+    /// [`alerts::expand_expr`].
+    pub expanded_expr: String,
+    pub for_: Option<String>,
+    pub severity: Option<String>,
+}
+
+/// A function argument, which becomes a metric label.
+#[derive(Debug)]
+pub struct FnArg {
+    /// Known label values, from a `#[values(...)]` attribute on the argument.
+    pub values: Option<Vec<String>>,
+    pub ident: Ident,
+    pub colon_token: Token![:],
+    pub ty: Type,
+}
+
+impl ToTokens for FnReturnTy {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let ty: Type = match self {
+            FnReturnTy::Counter => {
+                parse_quote! {
+                    ::metrics::Counter
+                }
+            }
+            FnReturnTy::Gauge => {
+                parse_quote! {
+                    ::metrics::Gauge
+                }
+            }
+            FnReturnTy::Histogram | FnReturnTy::Summary => {
+                parse_quote! {
+                    ::metrics::Histogram
+                }
+            }
+            // Info metrics and state sets are set by their generated function rather than returned.
+            FnReturnTy::Info | FnReturnTy::StateSet(_) => {
+                parse_quote! {
+                    ()
+                }
+            }
+        };
+        ty.to_tokens(tokens)
+    }
+}
+
+impl Mod {
+    /// Applies the macro arguments that change the module's metrics: adds the metrics they
+    /// declare, like `build_info`, and amends metric names. Also checks that qualified return
+    /// types go through `metrics` or its alias.
+    ///
+    /// The module is left expandable on errors: metrics with a wrongly qualified return type are
+    /// kept, as their kind is known, and a clashing `build_info` isn't added.
+    pub fn apply_args(&mut self, args: &MacroArgs) -> syn::Result<()> {
+        let mut errors = Errors::default();
+        for fn_ in &self.fns {
+            if let Some(qualifier) = &fn_.return_ty_qualifier {
+                errors.ok(check_return_ty_qualifier(
+                    qualifier,
+                    &fn_.fn_return_ty,
+                    args.metrics_alias.as_ref(),
+                ));
+            }
+        }
+
+        if let Some(build_info) = &args.build_info {
+            if self
+                .fns
+                .iter()
+                .any(|fn_| fn_.ident == BuildInfo::METRIC_NAME)
+            {
+                errors.push(syn::Error::new(
+                    build_info.span,
+                    "`build_info` is already declared in this module",
+                ));
+            } else {
+                self.fns.push(build_info.metric_fn());
+            }
+        }
+        if args.unit_suffixes {
+            for fn_ in &mut self.fns {
+                if let Some(unit) = fn_.attrs.unit.as_ref().and_then(units::Unit::from_expr) {
+                    fn_.metric_name = unit.with_name_suffix(&fn_.metric_name);
+                }
+            }
+        }
+        if let Some(conventions) = args.conventions {
+            for fn_ in &mut self.fns {
+                conventions.apply(fn_);
+            }
+        }
+        // Alerts refer to the metric by its final name, and to the quantiles the module declares.
+        let quantiles = args
+            .quantiles
+            .as_ref()
+            .map_or(
+                &Quantiles::DEFAULT[..],
+                |quantiles| &quantiles.quantiles[..],
+            );
+        for fn_ in &mut self.fns {
+            errors.ok(fn_.expand_alerts(Some(quantiles)));
+        }
+
+        errors.finish()
+    }
+}
+
+impl ItemFn {
+    /// Expands the metric's alert expressions, once its name is known. `quantiles` are those of
+    /// summaries, if the macro arguments are known yet.
+    fn expand_alerts(&mut self, quantiles: Option<&[f64]>) -> syn::Result<()> {
+        let mut errors = Errors::default();
+        let mut alerts = std::mem::take(&mut self.attrs.alerts);
+        for alert in &mut alerts {
+            match alerts::expand_expr(&alert.expr.value(), self, quantiles) {
+                Ok(expr) => alert.expanded_expr = expr,
+                Err(msg) => errors.push(syn::Error::new(alert.expr.span(), msg)),
+            }
+        }
+        self.attrs.alerts = alerts;
+
+        errors.finish()
+    }
+}
+
+fn check_return_ty_qualifier(
+    qualifier: &syn::Path,
+    ty: &FnReturnTy,
+    metrics_alias: Option<&syn::Path>,
+) -> syn::Result<()> {
+    let idents = |path: &syn::Path| {
+        path.segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>()
+    };
+    let qualifier_idents = idents(qualifier);
+    if qualifier_idents == ["metrics"]
+        || metrics_alias.map(idents).as_ref() == Some(&qualifier_idents)
+    {
+        return Ok(());
+    }
+
+    let leading_colon = if qualifier.leading_colon.is_some() {
+        "::"
+    } else {
+        ""
+    };
+    let qualifier_str = format!("{leading_colon}{}", qualifier_idents.join("::"));
+    let kind = ty.type_name();
+    Err(syn::Error::new(
+        qualifier.span(),
+        format!(
+            "`{qualifier_str}::{kind}` isn't a `metrics` type; write `metrics::{kind}`, or set `metrics_alias = \"{}\"` if `metrics` is reachable through `{qualifier_str}`",
+            qualifier_idents.join("::"),
+        ),
+    ))
+}
+
+impl BuildInfo {
+    pub const METRIC_NAME: &'static str = "build_info";
+
+    fn metric_fn(&self) -> ItemFn {
+        let labels = self.env_labels.iter().map(|(label, _)| label);
+        let mut fn_: ItemFn = parse_quote! {
+            #[description = "Build information: the crate version and build environment"]
+            pub fn build_info(version: &str, #(#labels: &str),*) -> Info;
+        };
+        fn_.attrs.build_info = Some(self.clone());
+
+        fn_
+    }
+}
+
+impl FnAttrs {
+    /// Label values `#[record_cancelled]` adds as the `outcome` label.
+    pub const OUTCOMES: [&'static str; 2] = ["completed", "cancelled"];
+}
+
+impl FnReturnTy {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            FnReturnTy::Counter => "Counter",
+            FnReturnTy::Gauge => "Gauge",
+            FnReturnTy::Histogram => "Histogram",
+            FnReturnTy::Summary => "Summary",
+            FnReturnTy::Info => "Info",
+            FnReturnTy::StateSet(_) => "StateSet",
+        }
+    }
+
+    pub fn kind(&self) -> catalog::MetricKind {
+        match self {
+            FnReturnTy::Counter => catalog::MetricKind::Counter,
+            FnReturnTy::Gauge => catalog::MetricKind::Gauge,
+            FnReturnTy::Histogram => catalog::MetricKind::Histogram,
+            FnReturnTy::Summary => catalog::MetricKind::Summary,
+            FnReturnTy::Info => catalog::MetricKind::Info,
+            FnReturnTy::StateSet(_) => catalog::MetricKind::StateSet,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Non-fatal issues with metric declarations: missing descriptions, metric names disagreeing with
+//! their unit or with the [`conventions`] the module opts into, and label types that tend to be
+//! high-cardinality.
+//!
+//! The macro reports them as compiler warnings, or as errors with
+//! `#[necessary_metrics(deny_warnings)]`.
+//!
+//! [`conventions`]: crate::conventions
+
+use proc_macro2::Span;
+use syn::Type;
+
+use crate::units::Unit;
+use crate::{MacroArgs, Mod};
+
+#[derive(Debug)]
+pub struct Warning {
+    pub span: Span,
+    pub message: String,
+}
+
+/// Label types whose values tend to be unbounded, like IDs or addresses.
+const HIGH_CARDINALITY_TYPES: [&str; 18] = [
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "u128",
+    "usize",
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "i128",
+    "isize",
+    "f32",
+    "f64",
+    "Uuid",
+    "IpAddr",
+    "Ipv4Addr",
+    "SocketAddr",
+];
+
+impl Warning {
+    pub fn new(span: Span, message: String) -> Self {
+        Self { span, message }
+    }
+
+    pub fn into_error(self) -> syn::Error {
+        syn::Error::new(self.span, self.message)
+    }
+}
+
+/// Checks the module once [`Mod::apply_args`] applied the macro arguments to it.
+pub fn check(mod_: &Mod, args: &MacroArgs) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    for fn_ in &mod_.fns {
+        let name = &fn_.metric_name;
+        if fn_.attrs.description.is_none() {
+            warnings.push(Warning::new(
+                fn_.ident.span(),
+                format!("`{name}` has no `#[description]`, so exporters show it without help text"),
+            ));
+        }
+        let unit = fn_.attrs.unit.as_ref().and_then(Unit::from_expr);
+        if let (Some(unit), Some(name_unit)) = (unit, Unit::from_name_suffix(name)) {
+            if unit != name_unit {
+                warnings.push(Warning::new(
+                    fn_.ident.span(),
+                    format!(
+                        "The name `{name}` suggests {}, but the unit is {}; rename the metric or change its unit",
+                        name_unit.as_str(),
+                        unit.as_str(),
+                    ),
+                ));
+            }
+        }
+        for arg in fn_.args.iter().filter(|arg| arg.values.is_none()) {
+            if let Some(ty) = high_cardinality_type(&arg.ty) {
+                warnings.push(Warning::new(
+                    arg.ident.span(),
+                    format!(
+                        "Labels of type `{ty}` tend to be high-cardinality, which makes a time series per value; \
+                         prefer a bounded set of values, listed with `#[values(...)]`"
+                    ),
+                ));
+            }
+        }
+        if let Some(conventions) = args.conventions {
+            conventions.check(fn_, &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+fn high_cardinality_type(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Reference(reference) => high_cardinality_type(&reference.elem),
+        Type::Path(path) => {
+            let ident = &path.path.segments.last()?.ident;
+            HIGH_CARDINALITY_TYPES
+                .contains(&ident.to_string().as_str())
+                .then(|| ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn check_messages(args: MacroArgs, mut mod_: Mod) -> Vec<String> {
+        mod_.apply_args(&args).unwrap();
+
+        check(&mod_, &args)
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect()
+    }
+
+    #[test]
+    fn missing_description() {
+        let mod_ = parse_quote! {
+            mod metrics {
+                pub fn requests() -> Counter;
+            }
+        };
+        assert_eq!(
+            check_messages(MacroArgs::default(), mod_),
+            ["`requests` has no `#[description]`, so exporters show it without help text"]
+        );
+    }
+
+    #[test]
+    fn name_suffix_must_agree_with_unit() {
+        let mod_ = parse_quote! {
+            mod metrics {
+                #[description = "latency"]
+                #[unit = metrics::Unit::Seconds]
+                pub fn latency_ms() -> Histogram;
+
+                #[description = "latency"]
+                #[unit = milliseconds]
+                pub fn query_latency_ms() -> Histogram;
+            }
+        };
+        assert_eq!(
+            check_messages(MacroArgs::default(), mod_),
+            ["The name `latency_ms` suggests milliseconds, but the unit is seconds; rename the metric or change its unit"]
+        );
+    }
+
+    #[test]
+    fn high_cardinality_labels() {
+        let mod_ = parse_quote! {
+            mod metrics {
+                #[description = "requests"]
+                pub fn requests(
+                    user_id: u64,
+                    peer: &std::net::SocketAddr,
+                    #[values("200", "500")] status: u16,
+                    route: &str,
+                ) -> Counter;
+            }
+        };
+        let messages = check_messages(MacroArgs::default(), mod_);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("Labels of type `u64` tend to be high-cardinality"));
+        assert!(messages[1].starts_with("Labels of type `SocketAddr` tend to be high-cardinality"));
+    }
+
+    #[test]
+    fn conventions() {
+        let args = parse_quote! { conventions = "prometheus" };
+        let mod_ = parse_quote! {
+            mod metrics {
+                #[description = "queue length"]
+                pub fn queue_length_total() -> Gauge;
+            }
+        };
+        assert_eq!(
+            check_messages(args, mod_),
+            ["`_total` is reserved for counters; rename `queue_length_total` to `queue_length`"]
+        );
+    }
+}
@@ -0,0 +1,845 @@
+use crate::catalog::CatalogFormat;
+use crate::common::{error, Errors};
+use crate::conventions::Conventions;
+use crate::units::Unit;
+use crate::{
+    Alert, Buckets, BuildInfo, Exporter, FnArg, FnReturnTy, MacroArgs, Quantiles, SummaryWindow,
+};
+
+use super::{FnAttrs, ItemFn, Mod, PartialMod};
+use proc_macro2::{Punct, Spacing, Span, TokenStream, TokenTree};
+use syn::parse::discouraged::Speculative;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{
+    braced, parenthesized, Attribute, Expr, ExprLit, ExprUnary, ForeignItemFn, GenericArgument,
+    Ident, Item, Lit, LitFloat, LitInt, LitStr, Meta, PathArguments, Token, Type, UnOp, Visibility,
+};
+
+const FN_ATTR_ERROR: &str =
+    "Unknown attribute; expected `#[cfg]`, `#[doc]`, `#[description]`, `#[unit]`, `#[alert(...)]`, `#[buckets(...)]`, `#[record_cancelled]`, or `#[typed]`";
+const FN_QUANTILES_ERROR: &str =
+    "Exporters compute the quantiles of every summary alike; set them with the `quantiles(...)` macro argument";
+const FN_WINDOW_ERROR: &str =
+    "Exporters compute every summary over the same window; set it with the `window(...)` macro argument";
+const METRIC_KIND_ERROR: &str =
+    "Only `Counter`, `Gauge`, `Histogram`, `Summary`, `Info`, and `StateSet<State>` are allowed as return types on functions, where `Counter`, `Gauge` and `Histogram` may be qualified, as in `metrics::Counter`";
+const STATE_SET_ERROR: &str =
+    "Expected `StateSet<State>`, where `State` is an enum deriving `necessary_metrics::StateSet`";
+const FN_ARG_ATTR_ERROR: &str = "Only `#[values(...)]` is allowed on function arguments";
+const MACRO_ARG_ERROR: &str =
+    "Unknown argument; expected `catalog`, `catalog = \"json\" | \"yaml\" | \"markdown\" | \"grafana\" | \"alerts\"`, `build_info(label = \"ENV_VAR\", ...)`, `unit_suffixes`, `conventions = \"prometheus\" | \"opentelemetry\"`, `metrics_alias = \"path\"`, `quantiles(...)`, `window(...)`, `exporter = \"prometheus\"`, or `deny_warnings`";
+
+impl Parse for MacroArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = MacroArgs::default();
+        let mut conventions_span = None;
+
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated(input)? {
+            if meta.path().is_ident("catalog") {
+                let format = match &meta {
+                    Meta::Path(_) => CatalogFormat::Json,
+                    Meta::NameValue(mnv) => match &mnv.value {
+                        Expr::Lit(expr) => match &expr.lit {
+                            Lit::Str(lit_str) if lit_str.value() == "json" => CatalogFormat::Json,
+                            Lit::Str(lit_str) if lit_str.value() == "yaml" => CatalogFormat::Yaml,
+                            Lit::Str(lit_str) if lit_str.value() == "markdown" => {
+                                CatalogFormat::Markdown
+                            }
+                            Lit::Str(lit_str) if lit_str.value() == "grafana" => {
+                                CatalogFormat::Grafana
+                            }
+                            Lit::Str(lit_str) if lit_str.value() == "alerts" => {
+                                CatalogFormat::AlertRules
+                            }
+                            _ => return error(&meta, MACRO_ARG_ERROR),
+                        },
+                        _ => return error(&meta, MACRO_ARG_ERROR),
+                    },
+                    Meta::List(_) => return error(&meta, MACRO_ARG_ERROR),
+                };
+                if args.catalog.contains(&format) {
+                    return error(&meta, "Metrics catalog format has already been requested");
+                }
+                args.catalog.push(format);
+            } else if meta.path().is_ident("build_info") {
+                if args.build_info.is_some() {
+                    return error(&meta, "Build info has already been requested");
+                }
+                let mut env_labels: Vec<(Ident, LitStr)> = Vec::new();
+                match &meta {
+                    Meta::Path(_) => {}
+                    Meta::List(list) => list.parse_nested_meta(|nested| {
+                        let label = nested.path.require_ident()?.clone();
+                        if label == "version" {
+                            return Err(nested.error(
+                                "The `version` label is always set, from `CARGO_PKG_VERSION`",
+                            ));
+                        }
+                        if env_labels.iter().any(|(existing, _)| *existing == label) {
+                            return Err(nested.error("Label has already been set"));
+                        }
+                        env_labels.push((label, nested.value()?.parse()?));
+                        Ok(())
+                    })?,
+                    Meta::NameValue(_) => return error(&meta, MACRO_ARG_ERROR),
+                }
+                args.build_info = Some(BuildInfo {
+                    env_labels,
+                    span: meta.span(),
+                });
+            } else if meta.path().is_ident("unit_suffixes") {
+                if !matches!(meta, Meta::Path(_)) {
+                    return error(&meta, MACRO_ARG_ERROR);
+                }
+                if args.unit_suffixes {
+                    return error(&meta, "Unit suffixes have already been requested");
+                }
+                args.unit_suffixes = true;
+            } else if meta.path().is_ident("deny_warnings") {
+                if !matches!(meta, Meta::Path(_)) {
+                    return error(&meta, MACRO_ARG_ERROR);
+                }
+                if args.deny_warnings {
+                    return error(&meta, "Warnings have already been denied");
+                }
+                args.deny_warnings = true;
+            } else if meta.path().is_ident("conventions") {
+                let conventions = match &meta {
+                    Meta::NameValue(mnv) => match &mnv.value {
+                        Expr::Lit(expr) => match &expr.lit {
+                            Lit::Str(lit_str) if lit_str.value() == "prometheus" => {
+                                Conventions::Prometheus
+                            }
+                            Lit::Str(lit_str) if lit_str.value() == "opentelemetry" => {
+                                Conventions::OpenTelemetry
+                            }
+                            _ => return error(&meta, MACRO_ARG_ERROR),
+                        },
+                        _ => return error(&meta, MACRO_ARG_ERROR),
+                    },
+                    _ => return error(&meta, MACRO_ARG_ERROR),
+                };
+                if args.conventions.is_some() {
+                    return error(&meta, "Conventions have already been set");
+                }
+                args.conventions = Some(conventions);
+                conventions_span = Some(meta.span());
+            } else if meta.path().is_ident("metrics_alias") {
+                let alias = match &meta {
+                    Meta::NameValue(mnv) => match &mnv.value {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(lit_str),
+                            ..
+                        }) => lit_str.parse::<syn::Path>()?,
+                        _ => return error(&meta, MACRO_ARG_ERROR),
+                    },
+                    _ => return error(&meta, MACRO_ARG_ERROR),
+                };
+                if args.metrics_alias.is_some() {
+                    return error(&meta, "The `metrics` alias has already been set");
+                }
+                args.metrics_alias = Some(alias);
+            } else if meta.path().is_ident("exporter") {
+                let exporter = match &meta {
+                    Meta::NameValue(mnv) => match &mnv.value {
+                        Expr::Lit(expr) => match &expr.lit {
+                            Lit::Str(lit_str) if lit_str.value() == "prometheus" => {
+                                Exporter::Prometheus
+                            }
+                            _ => return error(&meta, MACRO_ARG_ERROR),
+                        },
+                        _ => return error(&meta, MACRO_ARG_ERROR),
+                    },
+                    _ => return error(&meta, MACRO_ARG_ERROR),
+                };
+                if args.exporter.is_some() {
+                    return error(&meta, "The exporter has already been set");
+                }
+                args.exporter = Some(exporter);
+            } else if meta.path().is_ident("quantiles") {
+                if args.quantiles.is_some() {
+                    return error(&meta, "Summary quantiles have already been set");
+                }
+                args.quantiles = Some(parse_quantiles(&meta)?);
+            } else if meta.path().is_ident("window") {
+                if args.window.is_some() {
+                    return error(&meta, "Summary window has already been set");
+                }
+                args.window = Some(parse_window(&meta)?);
+            } else {
+                return error(&meta, MACRO_ARG_ERROR);
+            }
+        }
+
+        if let (true, Some(Conventions::OpenTelemetry), Some(span)) =
+            (args.unit_suffixes, args.conventions, conventions_span)
+        {
+            return Err(syn::Error::new(
+                span,
+                "OpenTelemetry conventions keep units out of metric names, so they can't be combined with `unit_suffixes`",
+            ));
+        }
+
+        Ok(args)
+    }
+}
+
+impl Parse for Mod {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let partial = Self::parse_partial(input)?;
+        match partial.errors {
+            Some(errors) => Err(errors),
+            None => Ok(partial.mod_),
+        }
+    }
+}
+
+impl Mod {
+    /// Parses the module, leaving out the functions that fail to parse so that the rest can still
+    /// expand.
+    pub fn parse_partial(input: ParseStream<'_>) -> syn::Result<PartialMod> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let mod_token = input.parse()?;
+        let ident = input.parse()?;
+        let content;
+        let _brace_token = braced!(content in input);
+        let inner_attrs = content.call(Attribute::parse_inner)?;
+
+        let mut fns = Vec::new();
+        let mut items = Vec::new();
+        let mut errors = Errors::default();
+        let mut unparsed_fns = Vec::new();
+        while !content.is_empty() {
+            // Items other than bodyless functions, which syn only parses as verbatim tokens, are
+            // passed through.
+            let fork = content.fork();
+            match fork.parse() {
+                Ok(Item::Verbatim(_)) | Err(_) => {}
+                Ok(item) => {
+                    content.advance_to(&fork);
+                    items.push(item);
+                    continue;
+                }
+            }
+            // Functions end at their `;`, so one failing to parse doesn't derail the next ones.
+            let mut item = content.step(|cursor| {
+                let mut tokens = Vec::new();
+                let mut rest = *cursor;
+                while let Some((tt, next)) = rest.token_tree() {
+                    rest = next;
+                    let is_end = matches!(&tt, TokenTree::Punct(punct) if punct.as_char() == ';');
+                    tokens.push(tt);
+                    if is_end {
+                        break;
+                    }
+                }
+                Ok((tokens, rest))
+            })?;
+            // Only the last function can miss its `;`: report it there rather than at the end of
+            // the input, which is the macro call site, and parse the function as if it had one.
+            match item.last() {
+                Some(TokenTree::Punct(punct)) if punct.as_char() == ';' => {}
+                last => {
+                    let span = last.map_or_else(Span::call_site, TokenTree::span);
+                    errors.push(syn::Error::new(span, "expected `;`"));
+                    let mut semi = Punct::new(';', Spacing::Alone);
+                    semi.set_span(span);
+                    item.push(semi.into());
+                }
+            }
+            let item: TokenStream = item.into_iter().collect();
+            match syn::parse2(item.clone()) {
+                Ok(fn_) => fns.push(fn_),
+                Err(error) => {
+                    errors.push(error);
+                    unparsed_fns.extend(syn::parse2::<ForeignItemFn>(item).ok());
+                }
+            }
+        }
+
+        let mod_ = Self {
+            attrs,
+            inner_attrs,
+            vis,
+            mod_token,
+            ident,
+            fns,
+            items,
+        };
+
+        Ok(PartialMod {
+            mod_,
+            errors: errors.into_error(),
+            unparsed_fns,
+        })
+    }
+}
+
+impl Parse for ItemFn {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        /// Parse attributes applied to a function item. Remember Rust docs get exposed via
+        /// `#[doc]` attributes:
+        /// <https://docs.rs/syn/latest/syn/struct.Attribute.html#doc-comments>
+        ///
+        /// Attributes that fail to parse are left out, with their errors collected in `errors`.
+        fn parse_attrs(attrs: Vec<Attribute>, errors: &mut Errors) -> FnAttrs {
+            let mut cfg = Vec::new();
+            let mut doc = "".to_owned();
+            let mut description = None;
+            let mut unit = None;
+            let mut alerts = Vec::new();
+            let mut buckets = None;
+            let mut record_cancelled = None;
+            let mut typed = None;
+
+            /// Reads as a string the value after the equals sign of an `Attribute` whose path is
+            /// of kind `Meta::NameValue`, e.g. an attribute like:
+            ///
+            ///     #[doc = "value"]
+            ///     fn fn_item() { }
+            ///
+            /// See <https://docs.rs/syn/latest/syn/struct.Attribute.html>.
+            fn read_attr_meta_name_value(attr: &Attribute) -> syn::Result<Option<String>> {
+                let mnv = attr.meta.require_name_value()?;
+                if let Expr::Lit(expr) = &mnv.value {
+                    if let Lit::Str(lit_str) = &expr.lit {
+                        return Ok(Some(lit_str.value()));
+                    }
+                }
+                Ok(None)
+            }
+
+            /// Reads as an expression the value after the equals sign of an `Attribute` whose path is
+            /// of kind `Meta::NameValue`.
+            fn read_attr_expr(attr: Attribute) -> syn::Result<Expr> {
+                let mnv = attr.meta.require_name_value()?;
+                Ok(mnv.value.clone())
+            }
+
+            let mut unit_attr = None;
+            for attr in attrs {
+                let result = (|| {
+                    if attr.path().is_ident("cfg") {
+                        cfg.push(attr);
+                    } else if attr.path().is_ident("doc") {
+                        if let Some(s) = read_attr_meta_name_value(&attr)? {
+                            // Like rustdoc, each `#[doc]` attribute is a separate line.
+                            if !doc.is_empty() {
+                                doc.push('\n');
+                            }
+                            doc.push_str(&s);
+                        }
+                    } else if attr.path().is_ident("description") {
+                        if description.is_some() {
+                            return error(&attr, "Metric description has already been set");
+                        }
+                        description = read_attr_expr(attr).ok();
+                    } else if attr.path().is_ident("unit") {
+                        if unit.is_some() {
+                            return error(&attr, "Metric unit has already been set");
+                        }
+                        unit_attr = Some(attr.clone());
+                        unit = read_attr_expr(attr).ok();
+                    } else if attr.path().is_ident("alert") {
+                        alerts.push(parse_alert(&attr)?);
+                    } else if attr.path().is_ident("buckets") {
+                        if buckets.is_some() {
+                            return error(&attr, "Histogram buckets have already been set");
+                        }
+                        buckets = Some(parse_buckets(&attr)?);
+                    } else if attr.path().is_ident("quantiles") {
+                        return error(&attr, FN_QUANTILES_ERROR);
+                    } else if attr.path().is_ident("window") {
+                        return error(&attr, FN_WINDOW_ERROR);
+                    } else if attr.path().is_ident("record_cancelled") {
+                        attr.meta.require_path_only()?;
+                        if record_cancelled.is_some() {
+                            return error(&attr, "`#[record_cancelled]` has already been set");
+                        }
+                        record_cancelled = Some(attr.span());
+                    } else if attr.path().is_ident("typed") {
+                        attr.meta.require_path_only()?;
+                        if typed.is_some() {
+                            return error(&attr, "`#[typed]` has already been set");
+                        }
+                        typed = Some(attr.span());
+                    } else {
+                        return error(&attr, FN_ATTR_ERROR);
+                    }
+
+                    Ok(())
+                })();
+                errors.ok(result);
+            }
+
+            if let (Some(unit_attr), None) = (&unit_attr, &description) {
+                errors.push(syn::Error::new(
+                    unit_attr.span(),
+                    "Cannot set metric unit without setting metric description",
+                ));
+            }
+
+            FnAttrs {
+                cfg,
+                doc,
+                description,
+                unit,
+                alerts,
+                buckets,
+                record_cancelled,
+                typed,
+                build_info: None,
+            }
+        }
+
+        /// Parses `#[buckets(...)]`, one of:
+        ///
+        ///     #[buckets(0.005, 0.01, 0.025)]
+        ///     #[buckets(exponential(start, factor, count))]
+        ///     #[buckets(linear(start, width, count))]
+        fn parse_buckets(attr: &Attribute) -> syn::Result<Buckets> {
+            fn parse_f64(expr: &Expr) -> syn::Result<f64> {
+                match expr {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Float(lit),
+                        ..
+                    }) => lit.base10_parse(),
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Int(lit), ..
+                    }) => lit.base10_parse(),
+                    Expr::Unary(ExprUnary {
+                        op: UnOp::Neg(_),
+                        expr,
+                        ..
+                    }) => Ok(-parse_f64(expr)?),
+                    _ => error(expr, "Expected a number literal"),
+                }
+            }
+
+            fn parse_count(expr: &Expr) -> syn::Result<usize> {
+                let count = match expr {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Int(lit), ..
+                    }) => lit.base10_parse()?,
+                    _ => return error(expr, "Expected an integer literal"),
+                };
+                if count == 0 {
+                    return error(expr, "Bucket count must be at least 1");
+                }
+
+                Ok(count)
+            }
+
+            let list = attr.meta.require_list()?;
+            let exprs = attr.parse_args_with(Punctuated::<Expr, Token![,]>::parse_terminated)?;
+            let bounds = match exprs.first() {
+                Some(Expr::Call(call)) if exprs.len() == 1 => {
+                    let generator = match &*call.func {
+                        Expr::Path(path) if path.path.is_ident("exponential") => "exponential",
+                        Expr::Path(path) if path.path.is_ident("linear") => "linear",
+                        _ => return error(&call.func, "Expected `exponential` or `linear`"),
+                    };
+                    let [start, step, count] = call.args.iter().collect::<Vec<_>>()[..] else {
+                        return error(call, "Expected `(start, factor or width, count)`");
+                    };
+                    let (start, step, count) =
+                        (parse_f64(start)?, parse_f64(step)?, parse_count(count)?);
+
+                    if generator == "exponential" {
+                        if start <= 0.0 || step <= 1.0 {
+                            return error(
+                                call,
+                                "Exponential buckets need a positive start and a factor greater than 1",
+                            );
+                        }
+                        (0..count)
+                            .map(|i| start * step.powi(i as i32))
+                            .collect::<Vec<_>>()
+                    } else {
+                        if step <= 0.0 {
+                            return error(call, "Linear buckets need a positive width");
+                        }
+                        (0..count)
+                            .map(|i| start + step * i as f64)
+                            .collect::<Vec<_>>()
+                    }
+                }
+                _ => exprs
+                    .iter()
+                    .map(parse_f64)
+                    .collect::<syn::Result<Vec<_>>>()?,
+            };
+
+            if bounds.is_empty() {
+                return error(list, "Histogram buckets must not be empty");
+            }
+            if bounds.iter().any(|bound| !bound.is_finite()) {
+                return error(list, "Histogram buckets must be finite");
+            }
+            if bounds.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return error(list, "Histogram buckets must be sorted in increasing order");
+            }
+
+            Ok(Buckets {
+                bounds,
+                span: attr.span(),
+            })
+        }
+
+        /// Parses `#[alert(name = "...", expr = "...", for = "...", severity = "...")]`, where
+        /// `for` and `severity` are optional.
+        fn parse_alert(attr: &Attribute) -> syn::Result<Alert> {
+            let mut name = None;
+            let mut expr = None;
+            let mut for_ = None;
+            let mut severity = None;
+            attr.parse_nested_meta(|meta| {
+                let slot = if meta.path.is_ident("name") {
+                    &mut name
+                } else if meta.path.is_ident("expr") {
+                    &mut expr
+                } else if meta.path.is_ident("for") {
+                    &mut for_
+                } else if meta.path.is_ident("severity") {
+                    &mut severity
+                } else {
+                    return Err(meta.error(
+                        "Unknown alert key; expected `name`, `expr`, `for`, or `severity`",
+                    ));
+                };
+                if slot.is_some() {
+                    return Err(meta.error("Alert key has already been set"));
+                }
+                *slot = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            })?;
+
+            let Some(name) = name else {
+                return error(attr, "Alert requires a `name`");
+            };
+            let Some(expr) = expr else {
+                return error(attr, "Alert requires an `expr`");
+            };
+
+            Ok(Alert {
+                name: name.value(),
+                expr,
+                // Expanded once the signature is parsed.
+                expanded_expr: String::new(),
+                for_: for_.map(|lit| lit.value()),
+                severity: severity.map(|lit| lit.value()),
+            })
+        }
+
+        let mut errors = Errors::default();
+        let attrs = parse_attrs(input.call(Attribute::parse_outer)?, &mut errors);
+        // Errors in the signature leave nothing to check the attributes against.
+        let signature = (|| {
+            let vis: Visibility = input.parse()?;
+            let fn_token: Token![fn] = input.parse()?;
+            let ident: Ident = input.parse()?;
+            let args_content;
+            let _paren_token = parenthesized!(args_content in input);
+            let mut args = Punctuated::new();
+
+            while !args_content.is_empty() {
+                args.push_value(FnArg::parse_collecting(&args_content, &mut errors)?);
+
+                if args_content.is_empty() {
+                    break;
+                }
+
+                args.push_punct(args_content.parse()?);
+            }
+
+            let arrow_token: Token![->] = input.parse()?;
+            let ty: Type = input.parse()?;
+            let _semi_token = input.parse::<Token![;]>()?;
+
+            Ok((vis, fn_token, ident, args, arrow_token, ty))
+        })();
+        let (vis, fn_token, ident, args, arrow_token, ty) = match signature {
+            Ok(signature) => signature,
+            Err(error) => {
+                errors.push(error);
+                return Err(errors.into_error().expect("an error was just pushed"));
+            }
+        };
+        let Some((ty, return_ty_qualifier)) = errors.ok(FnReturnTy::from_type_qualified(ty)) else {
+            return Err(errors.into_error().expect("an error was just pushed"));
+        };
+
+        if let (Some(buckets), false) = (&attrs.buckets, matches!(ty, FnReturnTy::Histogram)) {
+            errors.push(syn::Error::new(
+                buckets.span,
+                "Buckets can only be set on histograms",
+            ));
+        }
+        if let Some(span) = attrs.typed {
+            if let FnReturnTy::Info | FnReturnTy::StateSet(_) = ty {
+                errors.push(syn::Error::new(
+                    span,
+                    "`#[typed]` can't be set on info metrics or state sets",
+                ));
+            }
+            if let (Some(unit), None) = (&attrs.unit, attrs.unit.as_ref().and_then(Unit::from_expr))
+            {
+                errors.push(syn::Error::new(
+                    unit.span(),
+                    "`#[typed]` metrics need a `metrics::Unit` variant as their unit, to convert values into it",
+                ));
+            }
+        }
+        if let Some(span) = attrs.record_cancelled {
+            let time_unit = attrs
+                .unit
+                .as_ref()
+                .and_then(Unit::from_expr)
+                .and_then(Unit::per_second);
+            if !matches!(ty, FnReturnTy::Histogram | FnReturnTy::Summary) || time_unit.is_none() {
+                errors.push(syn::Error::new(
+                    span,
+                    "`#[record_cancelled]` can only be set on histograms and summaries with a time unit",
+                ));
+            }
+            if let Some(arg) = args.iter().find(|arg: &&FnArg| arg.ident == "outcome") {
+                errors.push(syn::Error::new(
+                    arg.ident.span(),
+                    "The `outcome` label is reserved by `#[record_cancelled]`",
+                ));
+            }
+        }
+
+        if let FnReturnTy::StateSet(_) = ty {
+            if let Some(arg) = args.iter().find(|arg: &&FnArg| arg.ident == ident) {
+                errors.push(syn::Error::new(
+                    arg.ident.span(),
+                    "State sets label their states with the metric name, so no other label can share it",
+                ));
+            }
+        }
+
+        let mut fn_ = ItemFn {
+            attrs,
+            vis,
+            fn_token,
+            metric_name: ident.to_string(),
+            ident,
+            args,
+            arrow_token,
+            fn_return_ty: ty,
+            return_ty_qualifier,
+        };
+        // Quantile shorthands are checked once the macro arguments are applied.
+        errors.ok(fn_.expand_alerts(None));
+        errors.finish()?;
+
+        Ok(fn_)
+    }
+}
+
+/// Parses the `quantiles(0.5, 0.9, 0.99)` macro argument.
+fn parse_quantiles(meta: &Meta) -> syn::Result<Quantiles> {
+    let lits = meta
+        .require_list()?
+        .parse_args_with(Punctuated::<LitFloat, Token![,]>::parse_terminated)?;
+    let quantiles = lits
+        .iter()
+        .map(LitFloat::base10_parse)
+        .collect::<syn::Result<Vec<f64>>>()?;
+
+    if quantiles.is_empty() {
+        return error(meta, "Summary quantiles must not be empty");
+    }
+    if let Some(lit) = lits.iter().find(|lit| {
+        let quantile: f64 = lit.base10_parse().unwrap_or(f64::NAN);
+        !(0.0..=1.0).contains(&quantile)
+    }) {
+        return error(lit, "Quantiles must be between 0 and 1");
+    }
+    if quantiles.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return error(meta, "Summary quantiles must be sorted in increasing order");
+    }
+
+    Ok(Quantiles {
+        quantiles,
+        span: meta.span(),
+    })
+}
+
+/// Parses the `window(max_age = "10m", age_buckets = 5)` macro argument, where both keys are
+/// optional and `max_age` is a whole number of seconds (`s`), minutes (`m`) or hours (`h`).
+fn parse_window(meta: &Meta) -> syn::Result<SummaryWindow> {
+    let mut max_age_secs = None;
+    let mut age_buckets = None;
+    meta.require_list()?.parse_nested_meta(|meta| {
+        if meta.path.is_ident("max_age") {
+            if max_age_secs.is_some() {
+                return Err(meta.error("`max_age` has already been set"));
+            }
+            let lit: LitStr = meta.value()?.parse()?;
+            let value = lit.value();
+            let (amount, secs_per_unit) = if let Some(amount) = value.strip_suffix('s') {
+                (amount, 1)
+            } else if let Some(amount) = value.strip_suffix('m') {
+                (amount, 60)
+            } else if let Some(amount) = value.strip_suffix('h') {
+                (amount, 60 * 60)
+            } else {
+                (value.as_str(), 0)
+            };
+            match amount.parse::<u64>() {
+                Ok(amount) if amount > 0 && secs_per_unit > 0 => {
+                    match amount.checked_mul(secs_per_unit) {
+                        Some(secs) => {
+                            max_age_secs = Some(secs);
+                            Ok(())
+                        }
+                        None => Err(syn::Error::new(lit.span(), "Duration is too long")),
+                    }
+                }
+                _ => Err(syn::Error::new(
+                    lit.span(),
+                    "Expected a positive duration like \"30s\", \"10m\" or \"1h\"",
+                )),
+            }
+        } else if meta.path.is_ident("age_buckets") {
+            if age_buckets.is_some() {
+                return Err(meta.error("`age_buckets` has already been set"));
+            }
+            let lit: LitInt = meta.value()?.parse()?;
+            match lit.base10_parse::<u32>()? {
+                0 => Err(syn::Error::new(
+                    lit.span(),
+                    "Age buckets must be at least 1",
+                )),
+                n => {
+                    age_buckets = Some(n);
+                    Ok(())
+                }
+            }
+        } else {
+            Err(meta.error("Unknown window key; expected `max_age` or `age_buckets`"))
+        }
+    })?;
+
+    Ok(SummaryWindow {
+        max_age_secs: max_age_secs.unwrap_or(SummaryWindow::DEFAULT_MAX_AGE_SECS),
+        age_buckets: age_buckets.unwrap_or(SummaryWindow::DEFAULT_AGE_BUCKETS),
+        span: meta.span(),
+    })
+}
+
+impl Parse for FnReturnTy {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Self::from_type(input.parse()?)
+    }
+}
+
+impl FnReturnTy {
+    pub fn from_type(ty: Type) -> syn::Result<Self> {
+        Self::from_type_qualified(ty).map(|(kind, _)| kind)
+    }
+
+    /// Like [`FnReturnTy::from_type`], also returning the path qualifying a `metrics` type, like
+    /// `metrics` in `metrics::Counter`.
+    fn from_type_qualified(ty: Type) -> syn::Result<(Self, Option<syn::Path>)> {
+        match ty {
+            Type::Path(ty) if is_state_set(&ty.path) => {
+                let PathArguments::AngleBracketed(args) = &ty.path.segments[0].arguments else {
+                    return error(&ty, STATE_SET_ERROR);
+                };
+                match args.args.iter().collect::<Vec<_>>()[..] {
+                    [GenericArgument::Type(state_ty)] => {
+                        Ok((Self::StateSet(Box::new(state_ty.clone())), None))
+                    }
+                    _ => error(&ty, STATE_SET_ERROR),
+                }
+            }
+            Type::Path(ty) if ty.qself.is_none() => {
+                let path = &ty.path;
+                let segments: Vec<_> = path.segments.iter().collect();
+                let Some((last, qualifier)) = segments.split_last() else {
+                    return error(&ty, METRIC_KIND_ERROR);
+                };
+                // Metric types take no generic arguments, and `::Counter` would name a crate.
+                if !last.arguments.is_none()
+                    || (path.leading_colon.is_some() && qualifier.is_empty())
+                {
+                    return error(&ty, METRIC_KIND_ERROR);
+                }
+                let qualifier = (!qualifier.is_empty()).then(|| syn::Path {
+                    leading_colon: path.leading_colon,
+                    segments: qualifier.iter().map(|segment| (*segment).clone()).collect(),
+                });
+
+                // `Summary` and `Info` aren't `metrics` types, so they can't be qualified.
+                let kind = match (last.ident.to_string().as_str(), &qualifier) {
+                    ("Counter", _) => Self::Counter,
+                    ("Gauge", _) => Self::Gauge,
+                    ("Histogram", _) => Self::Histogram,
+                    ("Summary", None) => Self::Summary,
+                    ("Info", None) => Self::Info,
+                    _ => {
+                        return error(&ty, METRIC_KIND_ERROR);
+                    }
+                };
+
+                Ok((kind, qualifier))
+            }
+            _ => error(&ty, METRIC_KIND_ERROR),
+        }
+    }
+}
+
+/// Whether the path is `StateSet<...>`, which is generated rather than a `metrics` type, so it
+/// can't be qualified.
+fn is_state_set(path: &syn::Path) -> bool {
+    path.leading_colon.is_none() && path.segments.len() == 1 && path.segments[0].ident == "StateSet"
+}
+
+impl Parse for FnArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut errors = Errors::default();
+        let arg = Self::parse_collecting(input, &mut errors)?;
+        errors.finish()?;
+
+        Ok(arg)
+    }
+}
+
+impl FnArg {
+    /// Parses the argument, collecting errors in its attributes in `errors` rather than failing.
+    fn parse_collecting(input: ParseStream, errors: &mut Errors) -> syn::Result<Self> {
+        let mut values = None;
+        for attr in input.call(Attribute::parse_outer)? {
+            let result = (|| {
+                if !attr.path().is_ident("values") {
+                    return error(&attr, FN_ARG_ATTR_ERROR);
+                }
+                if values.is_some() {
+                    return error(&attr, "Label values have already been set");
+                }
+                let lit_strs =
+                    attr.parse_args_with(Punctuated::<LitStr, Token![,]>::parse_terminated)?;
+                values = Some(lit_strs.iter().map(LitStr::value).collect());
+
+                Ok(())
+            })();
+            errors.ok(result);
+        }
+        let ident = input.parse()?;
+        let colon_token = input.parse()?;
+        let ty = input.parse()?;
+
+        Ok(Self {
+            values,
+            ident,
+            colon_token,
+            ty,
+        })
+    }
+}
@@ -0,0 +1,315 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote, ToTokens};
+use syn::Expr;
+
+/// Mirror of [`metrics::Unit`], used to statically recognise the expression given in a
+/// `#[unit = ...]` attribute.
+///
+/// [`metrics::Unit`]: https://docs.rs/metrics/latest/metrics/enum.Unit.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Count,
+    Percent,
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+    Tebibytes,
+    Gigibytes,
+    Mebibytes,
+    Kibibytes,
+    Bytes,
+    TerabitsPerSecond,
+    GigabitsPerSecond,
+    MegabitsPerSecond,
+    KilobitsPerSecond,
+    BitsPerSecond,
+    CountPerSecond,
+}
+
+impl Unit {
+    const ALL: [Unit; 17] = [
+        Unit::Count,
+        Unit::Percent,
+        Unit::Seconds,
+        Unit::Milliseconds,
+        Unit::Microseconds,
+        Unit::Nanoseconds,
+        Unit::Tebibytes,
+        Unit::Gigibytes,
+        Unit::Mebibytes,
+        Unit::Kibibytes,
+        Unit::Bytes,
+        Unit::TerabitsPerSecond,
+        Unit::GigabitsPerSecond,
+        Unit::MegabitsPerSecond,
+        Unit::KilobitsPerSecond,
+        Unit::BitsPerSecond,
+        Unit::CountPerSecond,
+    ];
+
+    /// Recognises expressions that are paths ending in a `metrics::Unit` variant, like
+    /// `metrics::Unit::Seconds` or `Unit::Seconds`, and the short forms named after
+    /// [`Unit::as_str`], like `seconds`. Anything else is opaque to the macro.
+    pub fn from_expr(expr: &Expr) -> Option<Self> {
+        let Expr::Path(expr_path) = expr else {
+            return None;
+        };
+        let segments = &expr_path.path.segments;
+        if expr_path.path.leading_colon.is_none() && segments.len() == 1 {
+            return Self::from_short_form(expr);
+        }
+        if segments.len() < 2 || segments[segments.len() - 2].ident != "Unit" {
+            return None;
+        }
+        let variant = segments.last()?.ident.to_string();
+
+        Self::ALL
+            .into_iter()
+            .find(|unit| unit.variant_name() == variant)
+    }
+
+    /// Recognises the short forms, like `seconds`, which aren't valid Rust expressions so have to
+    /// be expanded to their variant.
+    pub fn from_short_form(expr: &Expr) -> Option<Self> {
+        let Expr::Path(expr_path) = expr else {
+            return None;
+        };
+        let short_form = expr_path.path.get_ident()?.to_string();
+
+        Self::ALL
+            .into_iter()
+            .find(|unit| unit.as_str() == short_form)
+    }
+
+    /// Recognises metric names ending in a unit, like `latency_ms`, ignoring the `_total` suffix
+    /// of counters.
+    pub fn from_name_suffix(name: &str) -> Option<Self> {
+        let name = name.strip_suffix("_total").unwrap_or(name);
+
+        // The longest suffix wins, so `_bits_per_second` isn't read as `_per_second`.
+        Self::ALL
+            .into_iter()
+            .flat_map(|unit| {
+                unit.name_suffixes()
+                    .iter()
+                    .map(move |suffix| (unit, suffix))
+            })
+            .filter(|(_, suffix)| {
+                name.strip_suffix(*suffix)
+                    .is_some_and(|rest| rest.is_empty() || rest.ends_with('_'))
+            })
+            .max_by_key(|(_, suffix)| suffix.len())
+            .map(|(unit, _)| unit)
+    }
+
+    /// The suffixes metric names commonly use for this unit, canonical one first.
+    fn name_suffixes(self) -> &'static [&'static str] {
+        match self {
+            // Counts are the default, so names don't spell them out.
+            Unit::Count => &[],
+            Unit::Percent => &["percent", "pct"],
+            Unit::Seconds => &["seconds", "secs", "sec", "s"],
+            Unit::Milliseconds => &["milliseconds", "millis", "ms"],
+            Unit::Microseconds => &["microseconds", "micros", "us"],
+            Unit::Nanoseconds => &["nanoseconds", "nanos", "ns"],
+            Unit::Tebibytes => &["tebibytes", "tib"],
+            Unit::Gigibytes => &["gigibytes", "gibibytes", "gib"],
+            Unit::Mebibytes => &["mebibytes", "mib"],
+            Unit::Kibibytes => &["kibibytes", "kib"],
+            Unit::Bytes => &["bytes"],
+            Unit::TerabitsPerSecond => &["terabits_per_second", "tbps"],
+            Unit::GigabitsPerSecond => &["gigabits_per_second", "gbps"],
+            Unit::MegabitsPerSecond => &["megabits_per_second", "mbps"],
+            Unit::KilobitsPerSecond => &["kilobits_per_second", "kbps"],
+            Unit::BitsPerSecond => &["bits_per_second", "bps"],
+            Unit::CountPerSecond => &["count_per_second", "per_second"],
+        }
+    }
+
+    /// Appends the canonical suffix of this unit to `name`, before the `_total` suffix of
+    /// counters, unless the name already ends in a suffix of this unit.
+    pub fn with_name_suffix(self, name: &str) -> String {
+        let Some(suffix) = self.name_suffixes().first() else {
+            return name.to_owned();
+        };
+        if Self::from_name_suffix(name) == Some(self) {
+            return name.to_owned();
+        }
+
+        match name.strip_suffix("_total") {
+            Some(base) => format!("{base}_{suffix}_total"),
+            None => format!("{name}_{suffix}"),
+        }
+    }
+
+    fn variant_name(self) -> &'static str {
+        match self {
+            Unit::Count => "Count",
+            Unit::Percent => "Percent",
+            Unit::Seconds => "Seconds",
+            Unit::Milliseconds => "Milliseconds",
+            Unit::Microseconds => "Microseconds",
+            Unit::Nanoseconds => "Nanoseconds",
+            Unit::Tebibytes => "Tebibytes",
+            Unit::Gigibytes => "Gigibytes",
+            Unit::Mebibytes => "Mebibytes",
+            Unit::Kibibytes => "Kibibytes",
+            Unit::Bytes => "Bytes",
+            Unit::TerabitsPerSecond => "TerabitsPerSecond",
+            Unit::GigabitsPerSecond => "GigabitsPerSecond",
+            Unit::MegabitsPerSecond => "MegabitsPerSecond",
+            Unit::KilobitsPerSecond => "KilobitsPerSecond",
+            Unit::BitsPerSecond => "BitsPerSecond",
+            Unit::CountPerSecond => "CountPerSecond",
+        }
+    }
+
+    /// How many of this unit make a second, for time units.
+    pub fn per_second(self) -> Option<f64> {
+        match self {
+            Unit::Seconds => Some(1.0),
+            Unit::Milliseconds => Some(1e3),
+            Unit::Microseconds => Some(1e6),
+            Unit::Nanoseconds => Some(1e9),
+            _ => None,
+        }
+    }
+
+    /// How many bytes make one of this unit, for byte units.
+    pub fn bytes(self) -> Option<u64> {
+        match self {
+            Unit::Bytes => Some(1),
+            Unit::Kibibytes => Some(1 << 10),
+            Unit::Mebibytes => Some(1 << 20),
+            Unit::Gigibytes => Some(1 << 30),
+            Unit::Tebibytes => Some(1 << 40),
+            _ => None,
+        }
+    }
+
+    /// The same string `metrics::Unit::as_str` returns.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Unit::Count => "count",
+            Unit::Percent => "percent",
+            Unit::Seconds => "seconds",
+            Unit::Milliseconds => "milliseconds",
+            Unit::Microseconds => "microseconds",
+            Unit::Nanoseconds => "nanoseconds",
+            Unit::Tebibytes => "tebibytes",
+            Unit::Gigibytes => "gigibytes",
+            Unit::Mebibytes => "mebibytes",
+            Unit::Kibibytes => "kibibytes",
+            Unit::Bytes => "bytes",
+            Unit::TerabitsPerSecond => "terabits_per_second",
+            Unit::GigabitsPerSecond => "gigabits_per_second",
+            Unit::MegabitsPerSecond => "megabits_per_second",
+            Unit::KilobitsPerSecond => "kilobits_per_second",
+            Unit::BitsPerSecond => "bits_per_second",
+            Unit::CountPerSecond => "count_per_second",
+        }
+    }
+}
+
+/// Expands to the `metrics::Unit` variant, so that short forms like `seconds` expand to a valid
+/// expression.
+impl ToTokens for Unit {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let variant = format_ident!("{}", self.variant_name());
+        tokens.extend(quote! { ::metrics::Unit::#variant });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn recognises_unit_paths() {
+        let expr: Expr = parse_quote! { metrics::Unit::Milliseconds };
+        assert_eq!(Unit::from_expr(&expr), Some(Unit::Milliseconds));
+
+        let expr: Expr = parse_quote! { Unit::CountPerSecond };
+        assert_eq!(Unit::from_expr(&expr), Some(Unit::CountPerSecond));
+    }
+
+    #[test]
+    fn recognises_short_forms() {
+        let expr: Expr = parse_quote! { seconds };
+        assert_eq!(Unit::from_expr(&expr), Some(Unit::Seconds));
+
+        let expr: Expr = parse_quote! { bits_per_second };
+        assert_eq!(Unit::from_expr(&expr), Some(Unit::BitsPerSecond));
+
+        let expr: Expr = parse_quote! { ::seconds };
+        assert_eq!(Unit::from_expr(&expr), None);
+    }
+
+    #[test]
+    fn recognises_name_suffixes() {
+        assert_eq!(
+            Unit::from_name_suffix("latency_ms"),
+            Some(Unit::Milliseconds)
+        );
+        assert_eq!(
+            Unit::from_name_suffix("request_duration_seconds"),
+            Some(Unit::Seconds)
+        );
+        assert_eq!(
+            Unit::from_name_suffix("sent_bytes_total"),
+            Some(Unit::Bytes)
+        );
+        assert_eq!(
+            Unit::from_name_suffix("uplink_kilobits_per_second"),
+            Some(Unit::KilobitsPerSecond)
+        );
+        assert_eq!(
+            Unit::from_name_suffix("requests_per_second"),
+            Some(Unit::CountPerSecond)
+        );
+        assert_eq!(Unit::from_name_suffix("status"), None);
+        assert_eq!(Unit::from_name_suffix("items"), None);
+    }
+
+    #[test]
+    fn appends_canonical_name_suffixes() {
+        assert_eq!(Unit::Seconds.with_name_suffix("latency"), "latency_seconds");
+        assert_eq!(
+            Unit::Bytes.with_name_suffix("sent_total"),
+            "sent_bytes_total"
+        );
+        assert_eq!(
+            Unit::Milliseconds.with_name_suffix("latency_ms"),
+            "latency_ms"
+        );
+        assert_eq!(Unit::Count.with_name_suffix("requests"), "requests");
+    }
+
+    #[test]
+    fn arbitrary_expressions_are_not_recognised() {
+        let expr: Expr = parse_quote! { Seconds };
+        assert_eq!(Unit::from_expr(&expr), None);
+
+        let expr: Expr = parse_quote! { my_unit() };
+        assert_eq!(Unit::from_expr(&expr), None);
+
+        let expr: Expr = parse_quote! { metrics::Unit::Fortnights };
+        assert_eq!(Unit::from_expr(&expr), None);
+    }
+
+    #[test]
+    fn time_units_scale_to_seconds() {
+        assert_eq!(Unit::Seconds.per_second(), Some(1.0));
+        assert_eq!(Unit::Microseconds.per_second(), Some(1e6));
+        assert_eq!(Unit::Bytes.per_second(), None);
+    }
+
+    #[test]
+    fn byte_units_scale_to_bytes() {
+        assert_eq!(Unit::Bytes.bytes(), Some(1));
+        assert_eq!(Unit::Mebibytes.bytes(), Some(1024 * 1024));
+        assert_eq!(Unit::Seconds.bytes(), None);
+    }
+}
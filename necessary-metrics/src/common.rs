@@ -1,7 +1,22 @@
-use syn::spanned::Spanned;
+use necessary_metrics_core::lints::Warning;
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
 
-pub(crate) fn error<T>(spanned: &impl Spanned, msg: &'static str) -> syn::Result<T> {
-    Err(syn::Error::new(spanned.span(), msg))
+/// Surfaces a warning at its span, since proc macros can't emit warnings on stable: uses a
+/// deprecated item there, so rustc reports the message as a `deprecated` warning.
+pub(crate) fn warning(warning: Warning) -> TokenStream {
+    let Warning { span, message } = warning;
+    let item = Ident::new("necessary_metrics_warning", Span::call_site());
+    let use_ = Ident::new("necessary_metrics_warning", span);
+
+    quote! {
+        const _: () = {
+            #[deprecated(note = #message)]
+            #[allow(non_camel_case_types)]
+            struct #item;
+            let _ = #use_;
+        };
+    }
 }
 
 #[cfg(test)]
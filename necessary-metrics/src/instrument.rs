@@ -0,0 +1,316 @@
+//! `#[instrument(...)]`: times a function into a declared histogram, and optionally counts its
+//! outcomes into a declared counter.
+//!
+//! The attribute can't see the metric declarations, so it expands to calls to the functions the
+//! `#[necessary_metrics]` macro generates next to them: `start_<metric>` (or
+//! `start_<metric>_with`, passing a `<Metric>Labels` struct) for the histogram and `<metric>_with`
+//! for the counter. Whether the metrics exist, have a time unit, and declare exactly the labels
+//! given is then checked by the compiler.
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::visit_mut::{self, VisitMut};
+use syn::{parenthesized, Expr, Ident, ItemFn, Path, ReturnType, Token, Type, TypeInfer};
+
+const INSTRUMENT_ARG_ERROR: &str =
+    "Unknown argument; expected `labels(key = value, ...)` or `counter = path::to::metric`";
+
+/// Arguments to `#[instrument(histogram, labels(...), counter = ...)]`.
+#[derive(Debug)]
+pub(crate) struct InstrumentArgs {
+    histogram: Path,
+    labels: Vec<(Ident, Expr)>,
+    counter: Option<Path>,
+}
+
+/// A `key = value` label in `labels(...)`.
+struct LabelArg {
+    key: Ident,
+    value: Expr,
+}
+
+impl Parse for LabelArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let key = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+
+        Ok(Self { key, value })
+    }
+}
+
+impl Parse for InstrumentArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let histogram = input.parse()?;
+        let mut labels: Option<Vec<(Ident, Expr)>> = None;
+        let mut counter = None;
+
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+
+            let key: Ident = input.parse()?;
+            if key == "labels" {
+                if labels.is_some() {
+                    return Err(syn::Error::new(key.span(), "Labels have already been set"));
+                }
+                let content;
+                parenthesized!(content in input);
+                let mut parsed: Vec<(Ident, Expr)> = Vec::new();
+                for label in Punctuated::<LabelArg, Token![,]>::parse_terminated(&content)? {
+                    if parsed.iter().any(|(key, _)| *key == label.key) {
+                        return Err(syn::Error::new(
+                            label.key.span(),
+                            "Label has already been set",
+                        ));
+                    }
+                    if label.key == "outcome" {
+                        return Err(syn::Error::new(
+                            label.key.span(),
+                            "The `outcome` label is set from the function's result",
+                        ));
+                    }
+                    parsed.push((label.key, label.value));
+                }
+                labels = Some(parsed);
+            } else if key == "counter" {
+                if counter.is_some() {
+                    return Err(syn::Error::new(key.span(), "Counter has already been set"));
+                }
+                input.parse::<Token![=]>()?;
+                counter = Some(input.parse()?);
+            } else {
+                return Err(syn::Error::new(key.span(), INSTRUMENT_ARG_ERROR));
+            }
+        }
+
+        Ok(Self {
+            histogram,
+            labels: labels.unwrap_or_default(),
+            counter,
+        })
+    }
+}
+
+/// Replaces `impl Trait` types with `_`.
+struct InferImplTrait;
+
+impl VisitMut for InferImplTrait {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if let Type::ImplTrait(impl_trait) = ty {
+            *ty = Type::Infer(TypeInfer {
+                underscore_token: Token![_](impl_trait.impl_token.span),
+            });
+        } else {
+            visit_mut::visit_type_mut(self, ty);
+        }
+    }
+}
+
+/// `app_metrics::request_duration` becomes e.g. `app_metrics::start_request_duration_with`,
+/// keeping the span of the metric name so errors point at it.
+fn sibling(path: &Path, name: impl FnOnce(&str) -> String) -> Path {
+    let mut path = path.clone();
+    let last = path
+        .segments
+        .last_mut()
+        .expect("paths have at least one segment");
+    last.ident = Ident::new(&name(&last.ident.to_string()), last.ident.span());
+
+    path
+}
+
+/// The `<Metric>Labels` struct built with the given labels, for `<metric>_with` functions.
+fn labels_struct(metric: &Path, fields: &[TokenStream]) -> TokenStream {
+    let struct_path = sibling(metric, |name| format!("{}Labels", crate::camel_case(name)));
+    quote! { #struct_path { #(#fields,)* } }
+}
+
+pub(crate) fn expand(args: InstrumentArgs, item: ItemFn) -> TokenStream {
+    let InstrumentArgs {
+        histogram,
+        labels,
+        counter,
+    } = args;
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = item;
+
+    // Label values are evaluated once, on entry, into locals the function body can't see.
+    let locals: Vec<_> = labels
+        .iter()
+        .map(|(key, _)| Ident::new(&format!("label_{key}"), Span::mixed_site()))
+        .collect();
+    let values = labels.iter().map(|(_, value)| value);
+    let fields: Vec<_> = labels
+        .iter()
+        .zip(&locals)
+        .map(|((key, _), local)| quote! { #key: #local })
+        .collect();
+
+    let timer = Ident::new("timer", Span::mixed_site());
+    let output = Ident::new("output", Span::mixed_site());
+
+    let start_timer = if labels.is_empty() {
+        let start_fn = sibling(&histogram, |name| format!("start_{name}"));
+        quote! { #start_fn() }
+    } else {
+        let start_fn = sibling(&histogram, |name| format!("start_{name}_with"));
+        let labels = labels_struct(&histogram, &fields);
+        quote! { #start_fn(#labels) }
+    };
+
+    // `impl Trait` can't annotate the closure or the binding, so it's left to inference, while
+    // the rest of the type still guides `?` conversions.
+    let output_ty = match &sig.output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => {
+            let mut ty = (**ty).clone();
+            InferImplTrait.visit_type_mut(&mut ty);
+            quote! { #ty }
+        }
+    };
+    let call = if sig.asyncness.is_some() {
+        quote! { async move #block.await }
+    } else {
+        quote! { (move || -> #output_ty #block)() }
+    };
+
+    let count_outcome = match counter {
+        Some(counter) => {
+            let with_fn = sibling(&counter, |name| format!("{name}_with"));
+            let mut fields = fields.clone();
+            fields.push(quote! {
+                outcome: if #output.is_ok() { "ok" } else { "error" }
+            });
+            let labels = labels_struct(&counter, &fields);
+            quote! { #with_fn(#labels).increment(1); }
+        }
+        None => quote! {},
+    };
+
+    quote! {
+        #(#attrs)*
+        #vis #sig {
+            #(let #locals = #values;)*
+            let #timer = #start_timer;
+            let #output: #output_ty = #call;
+            #timer.stop();
+            #count_outcome
+            #output
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_utils::code_str;
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    #[test]
+    fn expand_sync_fn() {
+        let args: InstrumentArgs = parse_quote! {
+            app_metrics::request_duration, labels(route = "list"), counter = app_metrics::requests
+        };
+        let item = parse_quote! {
+            /// Lists things.
+            pub fn list(limit: usize) -> Result<Vec<u8>, ()> {
+                Ok(vec![0; limit])
+            }
+        };
+        let actual = expand(args, item).to_string();
+
+        let expected = code_str! {
+            /// Lists things.
+            pub fn list(limit: usize) -> Result<Vec<u8>, ()> {
+                let label_route = "list";
+                let timer = app_metrics::start_request_duration_with(
+                    app_metrics::RequestDurationLabels { route: label_route, }
+                );
+                let output: Result<Vec<u8>, ()> = (move || -> Result<Vec<u8>, ()> {
+                    Ok(vec![0; limit])
+                })();
+                timer.stop();
+                app_metrics::requests_with(app_metrics::RequestsLabels {
+                    route: label_route,
+                    outcome: if output.is_ok() { "ok" } else { "error" },
+                })
+                .increment(1);
+                output
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn expand_async_fn() {
+        let args: InstrumentArgs = parse_quote! { app_metrics::poll_duration };
+        let item = parse_quote! {
+            async fn poll() {
+                tick().await;
+            }
+        };
+        let actual = expand(args, item).to_string();
+
+        let expected = code_str! {
+            async fn poll() {
+                let timer = app_metrics::start_poll_duration();
+                let output: () = async move {
+                    tick().await;
+                }
+                .await;
+                timer.stop();
+                output
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn impl_trait_is_inferred() {
+        let args: InstrumentArgs = parse_quote! { app_metrics::request_duration };
+        let item = parse_quote! {
+            fn digits() -> Result<impl Iterator<Item = u8>, ()> {
+                Ok(0..10)
+            }
+        };
+        let actual = expand(args, item).to_string();
+
+        let expected = code_str! {
+            fn digits() -> Result<impl Iterator<Item = u8>, ()> {
+                let timer = app_metrics::start_request_duration();
+                let output: Result<_, ()> = (move || -> Result<_, ()> {
+                    Ok(0..10)
+                })();
+                timer.stop();
+                output
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "The `outcome` label is set from the function's result")]
+    fn outcome_label_is_reserved() {
+        let _args: InstrumentArgs = parse_quote! {
+            app_metrics::request_duration, labels(outcome = "ok")
+        };
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown argument; expected `labels(key = value, ...)`")]
+    fn unknown_argument() {
+        let _args: InstrumentArgs = parse_quote! {
+            app_metrics::request_duration, gauge = app_metrics::in_flight
+        };
+    }
+}
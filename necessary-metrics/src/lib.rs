@@ -1,105 +1,309 @@
 #![doc = include_str!("../../readme.md")]
+use std::collections::HashSet;
+
+use necessary_metrics_core::{
+    catalog, lints, units, Exporter, FnArg, FnAttrs, FnReturnTy, ItemFn, MacroArgs, Mod,
+    PartialMod, Quantiles, SummaryWindow,
+};
 use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
-use syn::parse_quote;
-use syn::{
-    parse_macro_input, punctuated::Punctuated, Attribute, Expr, Ident, Token, Type, Visibility,
-};
+use syn::parse::Parser;
+use syn::{parse_macro_input, punctuated::Punctuated, Expr, ExprLit, Ident, Lit, Token};
 
 mod common;
-mod parsing;
-
-#[derive(Debug)]
-struct Mod {
-    attrs: Vec<Attribute>,
-    vis: Visibility,
-    mod_token: Token![mod],
-    ident: Ident,
-    fns: Vec<ItemFn>,
-}
+mod instrument;
+mod state_set;
 
-#[derive(Debug)]
-struct ItemFn {
-    attrs: FnAttrs,
-    vis: Visibility,
-    fn_token: Token![fn],
-    ident: Ident,
-    args: Punctuated<FnArg, Token![,]>,
-    arrow_token: Token![->],
-    fn_return_ty: FnReturnTy,
-}
+#[proc_macro_attribute]
+pub fn necessary_metrics(args: TokenStream, item: TokenStream) -> TokenStream {
+    // Functions that fail to parse are reported, and the rest still expand, so that IDEs and the
+    // rest of the crate keep working meanwhile.
+    let PartialMod {
+        mut mod_,
+        errors: mut parse_errors,
+        unparsed_fns,
+    } = match Mod::parse_partial.parse(item) {
+        Ok(partial) => partial,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    // Likewise, the module expands without the arguments when they fail to parse.
+    let args = match syn::parse::<MacroArgs>(args) {
+        Ok(args) => args,
+        Err(e) => {
+            match &mut parse_errors {
+                Some(errors) => errors.combine(e),
+                None => parse_errors = Some(e),
+            }
+            MacroArgs::default()
+        }
+    };
+    // The module still expands when the arguments don't apply cleanly, like parse errors.
+    if let Err(e) = mod_.apply_args(&args) {
+        match &mut parse_errors {
+            Some(errors) => errors.combine(e),
+            None => parse_errors = Some(e),
+        }
+    }
 
-#[derive(Debug)]
-enum FnReturnTy {
-    Counter,
-    Gauge,
-    Histogram,
-}
+    let mut ret = proc_macro2::TokenStream::new();
+    if let Some(e) = &parse_errors {
+        ret.extend(e.to_compile_error());
+    }
+    for warning in lints::check(&mod_, &args) {
+        ret.extend(match args.deny_warnings {
+            true => warning.into_error().to_compile_error(),
+            false => common::warning(warning),
+        });
+    }
+    // A catalog of the functions that did parse would be missing or misnaming metrics.
+    let catalog = match parse_errors {
+        Some(_) => &[][..],
+        None => &args.catalog[..],
+    };
+    let source_file = proc_macro::Span::call_site().local_file();
+    for &format in catalog {
+        if let Err(e) = catalog::write(&mod_, format, source_file.as_deref()) {
+            ret.extend(e.to_compile_error());
+        }
+    }
+    let stubs = unparsed_fns.iter().filter_map(expand_stub).collect();
+    ret.extend(expand_mod(mod_, &args, stubs));
 
-#[derive(Debug)]
-struct FnAttrs {
-    cfg: Vec<Attribute>,
-    doc: String,
-    description: Option<Expr>,
-    unit: Option<Expr>,
+    ret.into()
 }
 
-#[derive(Debug)]
-struct FnArg {
-    ident: Ident,
-    colon_token: Token![:],
-    ty: Type,
-}
+/// Times a function into a histogram declared with `#[necessary_metrics]`, and optionally counts
+/// its outcomes, `ok` or `error` from `Result::is_ok`, into a declared counter:
+///
+/// ```text
+/// #[necessary_metrics::instrument(
+///     app_metrics::request_duration,
+///     labels(route = "list"),
+///     counter = app_metrics::requests,
+/// )]
+/// async fn list() -> Result<Vec<Item>, Error> { ... }
+/// ```
+///
+/// The histogram must have a time unit, and both metrics must declare exactly the labels given,
+/// plus `outcome` on the counter.
+#[proc_macro_attribute]
+pub fn instrument(args: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as instrument::InstrumentArgs);
+    let item = parse_macro_input!(item as syn::ItemFn);
 
-impl ToTokens for FnReturnTy {
-    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        let ty: Type = match self {
-            FnReturnTy::Counter => {
-                parse_quote! {
-                    ::metrics::Counter
-                }
-            }
-            FnReturnTy::Gauge => {
-                parse_quote! {
-                    ::metrics::Gauge
-                }
-            }
-            FnReturnTy::Histogram => {
-                parse_quote! {
-                    ::metrics::Histogram
-                }
-            }
-        };
-        ty.to_tokens(tokens)
-    }
+    instrument::expand(args, item).into()
 }
 
-#[proc_macro_attribute]
-pub fn necessary_metrics(_args: TokenStream, item: TokenStream) -> TokenStream {
-    let mod_ = parse_macro_input!(item as Mod);
-    expand_from_parsed(mod_).into()
+/// Lets a fieldless enum be the states of a `StateSet<State>` metric, named after its variants
+/// in snake case.
+#[proc_macro_derive(StateSet)]
+pub fn derive_state_set(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+
+    state_set::expand_derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
 
+#[cfg(test)]
 fn expand_from_parsed(mod_: Mod) -> proc_macro2::TokenStream {
+    expand_mod(mod_, &MacroArgs::default(), vec![])
+}
+
+/// Expands the module, with `stubs` standing in for the functions that failed to parse.
+fn expand_mod(
+    mod_: Mod,
+    args: &MacroArgs,
+    stubs: Vec<proc_macro2::TokenStream>,
+) -> proc_macro2::TokenStream {
     let Mod {
         attrs: mod_attrs,
+        inner_attrs,
         vis: mod_vis,
         mod_token,
         ident: mod_name,
         fns,
+        items,
     } = mod_;
 
-    let metric_fns = fns.into_iter().map(|fn_| expand_metric_fn(fn_));
+    let histogram_buckets = expand_histogram_buckets(&fns);
+    let summary_settings = expand_summary_settings(&fns, args);
+    let exporter_config = expand_exporter_config(&fns, args);
+    let timer = expand_timer(&fns);
+    let in_flight_guard = expand_in_flight_guard(&fns);
+    let bytes = expand_bytes(&fns);
+    let metric_fns = fns.into_iter().map(expand_metric_fn);
+
+    let generated = quote! {
+        #(#metric_fns)*
+        #(#stubs)*
+        #histogram_buckets
+        #summary_settings
+        #exporter_config
+        #timer
+        #in_flight_guard
+        #bytes
+    };
+    let (items, clashes) = remove_clashing_items(items, &generated);
 
     let ret: proc_macro2::TokenStream = quote! {
+        #clashes
         #(#mod_attrs)* #mod_vis #mod_token #mod_name {
-            #(#metric_fns)*
+            #(#inner_attrs)*
+            #(#items)*
+            #generated
         }
     };
 
     ret
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Namespace {
+    Type,
+    Value,
+}
+
+/// Takes the items passed through that are named like a generated item out of the module, so
+/// that the clash is reported on them rather than as duplicate definitions at the macro call.
+fn remove_clashing_items(
+    items: Vec<syn::Item>,
+    generated: &proc_macro2::TokenStream,
+) -> (Vec<syn::Item>, proc_macro2::TokenStream) {
+    let generated_names: HashSet<_> = syn::parse2::<syn::File>(generated.clone())
+        .map(|file| {
+            file.items
+                .iter()
+                .flat_map(item_names)
+                .map(|(ident, namespace)| (ident.to_string(), namespace))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut errors = proc_macro2::TokenStream::new();
+    let items = items
+        .into_iter()
+        .filter(|item| {
+            let clash = item_names(item).into_iter().find(|(ident, namespace)| {
+                generated_names.contains(&(ident.to_string(), *namespace))
+            });
+            if let Some((ident, _)) = clash {
+                let message = format!(
+                    "`{ident}` is also generated by `#[necessary_metrics]` in this module; rename \
+                     this item or move it out of the module"
+                );
+                errors.extend(syn::Error::new(ident.span(), message).to_compile_error());
+            }
+            clash.is_none()
+        })
+        .collect();
+
+    (items, errors)
+}
+
+/// The names an item defines in the module.
+fn item_names(item: &syn::Item) -> Vec<(&Ident, Namespace)> {
+    fn use_names<'a>(tree: &'a syn::UseTree, names: &mut Vec<(&'a Ident, Namespace)>) {
+        match tree {
+            syn::UseTree::Path(path) => use_names(&path.tree, names),
+            syn::UseTree::Name(name) if name.ident != "self" => {
+                names.extend([
+                    (&name.ident, Namespace::Type),
+                    (&name.ident, Namespace::Value),
+                ]);
+            }
+            syn::UseTree::Rename(rename) => {
+                names.extend([
+                    (&rename.rename, Namespace::Type),
+                    (&rename.rename, Namespace::Value),
+                ]);
+            }
+            syn::UseTree::Group(group) => {
+                for tree in &group.items {
+                    use_names(tree, names);
+                }
+            }
+            syn::UseTree::Name(_) | syn::UseTree::Glob(_) => {}
+        }
+    }
+
+    match item {
+        syn::Item::Const(item) => vec![(&item.ident, Namespace::Value)],
+        syn::Item::Static(item) => vec![(&item.ident, Namespace::Value)],
+        syn::Item::Fn(item) => vec![(&item.sig.ident, Namespace::Value)],
+        // Tuple and unit structs also name their constructor.
+        syn::Item::Struct(item) => match item.fields {
+            syn::Fields::Named(_) => vec![(&item.ident, Namespace::Type)],
+            syn::Fields::Unnamed(_) | syn::Fields::Unit => vec![
+                (&item.ident, Namespace::Type),
+                (&item.ident, Namespace::Value),
+            ],
+        },
+        syn::Item::Enum(item) => vec![(&item.ident, Namespace::Type)],
+        syn::Item::Union(item) => vec![(&item.ident, Namespace::Type)],
+        syn::Item::Trait(item) => vec![(&item.ident, Namespace::Type)],
+        syn::Item::TraitAlias(item) => vec![(&item.ident, Namespace::Type)],
+        syn::Item::Type(item) => vec![(&item.ident, Namespace::Type)],
+        syn::Item::Mod(item) => vec![(&item.ident, Namespace::Type)],
+        syn::Item::ExternCrate(item) => {
+            let ident = item
+                .rename
+                .as_ref()
+                .map_or(&item.ident, |(_, rename)| rename);
+            vec![(ident, Namespace::Type)]
+        }
+        syn::Item::Use(item) => {
+            let mut names = Vec::new();
+            use_names(&item.tree, &mut names);
+            names
+        }
+        _ => vec![],
+    }
+}
+
+/// Stands in for a function that failed to parse, with the same signature but no metric, so
+/// that its callers still type-check while its errors are reported. Only functions returning a
+/// known metric kind get one.
+fn expand_stub(fn_: &syn::ForeignItemFn) -> Option<proc_macro2::TokenStream> {
+    let syn::ReturnType::Type(arrow_token, ty) = &fn_.sig.output else {
+        return None;
+    };
+    let fn_return_ty = FnReturnTy::from_type((**ty).clone()).ok()?;
+    let attrs = fn_
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg") || attr.path().is_ident("doc"));
+    // Label arguments may carry `#[values(...)]`, which isn't a real attribute.
+    let args = fn_
+        .sig
+        .inputs
+        .iter()
+        .map(|arg| match arg {
+            syn::FnArg::Typed(arg) => {
+                let (pat, colon_token, ty) = (&arg.pat, &arg.colon_token, &arg.ty);
+                Some(quote! { #pat #colon_token #ty })
+            }
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let state_param = match &fn_return_ty {
+        FnReturnTy::StateSet(state_ty) => vec![quote! { state: #state_ty }],
+        _ => vec![],
+    };
+    let vis = &fn_.vis;
+    let fn_token = &fn_.sig.fn_token;
+    let ident = &fn_.sig.ident;
+    let message = format!("`{ident}` failed to parse, see the errors of `#[necessary_metrics]`");
+
+    Some(quote! {
+        #(#attrs)*
+        #[allow(unused_variables)]
+        #vis #fn_token #ident(#(#args,)* #(#state_param,)*) #arrow_token #fn_return_ty {
+            ::core::unimplemented!(#message)
+        }
+    })
+}
+
 fn expand_metric_fn(fn_: ItemFn) -> proc_macro2::TokenStream {
     let ItemFn {
         attrs:
@@ -108,71 +312,419 @@ fn expand_metric_fn(fn_: ItemFn) -> proc_macro2::TokenStream {
                 doc,
                 description,
                 unit,
+                // Alerts only end up in the catalog.
+                alerts: _,
+                buckets,
+                record_cancelled,
+                typed,
+                build_info,
             },
         fn_token,
         vis: fn_vis,
         ident: metric_name_ident,
+        metric_name,
         args,
         arrow_token,
         fn_return_ty,
+        // Checked by `Mod::apply_args`, the metric is emitted through `::metrics` either way.
+        return_ty_qualifier: _,
     } = fn_;
 
-    let fn_args = args.iter().map(|arg| {
-        let FnArg {
-            ident: arg_name,
-            colon_token,
-            ty: arg_ty,
-            ..
-        } = arg;
+    let fn_args: Vec<_> = args
+        .iter()
+        .map(|arg| {
+            let FnArg {
+                ident: arg_name,
+                colon_token,
+                ty: arg_ty,
+                ..
+            } = arg;
 
-        quote! { #arg_name #colon_token #arg_ty }
-    });
+            quote! { #arg_name #colon_token #arg_ty }
+        })
+        .collect();
+    let arg_names: Vec<_> = args.iter().map(|arg| &arg.ident).collect();
 
     let label_cnt = args.len();
-    let label_emission = args.iter().map(|arg| {
-        let FnArg {
-            ident: arg_name, ..
-        } = arg;
-        let metric_key = arg_name.to_string();
+    let label_emission: Vec<_> = args
+        .iter()
+        .map(|arg| {
+            let FnArg {
+                ident: arg_name, ..
+            } = arg;
+            let metric_key = arg_name.to_string();
 
-        quote! {
-            (#metric_key, #arg_name.to_string())
-        }
+            quote! {
+                (#metric_key, #arg_name.to_string())
+            }
+        })
+        .collect();
+    // Generated items are named after the function, which can differ from the metric name.
+    let fn_name = metric_name_ident.to_string();
+    let metric_doc = metric_doc(
+        &metric_name,
+        &fn_return_ty,
+        unit.as_ref(),
+        description.as_ref(),
+        &args,
+    );
+    let time_unit = unit
+        .as_ref()
+        .and_then(units::Unit::from_expr)
+        .filter(|unit| unit.per_second().is_some());
+    // With `#[record_cancelled]`, every path but cancelled futures records as completed, so that
+    // the metric always has the same label set.
+    let completed_label = record_cancelled.map(|_| {
+        let [completed, _] = FnAttrs::OUTCOMES;
+        quote! { ("outcome", #completed.to_string()) }
     });
-    let metric_name = metric_name_ident.to_string();
-    let (labels_ref, labels_binding) = if label_cnt > 0 {
+    let (labels_ref, labels_binding) = if label_cnt > 0 || completed_label.is_some() {
         (
             quote! { &labels },
-            quote! { let labels = [#(#label_emission,)*]; },
+            quote! { let labels = [#(#label_emission,)* #completed_label]; },
         )
     } else {
         (quote! {}, quote! {})
     };
-    let metric_emission = match fn_return_ty {
+    // State sets take the current state after their labels. Its name can't clash with labels.
+    let state_ident = Ident::new("state", proc_macro2::Span::mixed_site());
+    let (state_param, state_arg) = match &fn_return_ty {
+        FnReturnTy::StateSet(state_ty) => (
+            vec![quote! { #state_ident: #state_ty }],
+            quote! { #state_ident, },
+        ),
+        _ => (vec![], quote! {}),
+    };
+    // Locals of state set setters, which can't shadow labels either.
+    let [lock_ident, current_ident, others_ident, name_ident, labels_ident] =
+        ["_lock", "current", "others", "name", "labels"]
+            .map(|name| Ident::new(name, proc_macro2::Span::mixed_site()));
+    let metric_emission = match &fn_return_ty {
         FnReturnTy::Counter => quote! { ::metrics::counter!(#metric_name, #labels_ref) },
         FnReturnTy::Gauge => quote! { ::metrics::gauge!(#metric_name, #labels_ref) },
-        FnReturnTy::Histogram => quote! { ::metrics::histogram!(#metric_name, #labels_ref) },
+        FnReturnTy::Histogram | FnReturnTy::Summary => {
+            quote! { ::metrics::histogram!(#metric_name, #labels_ref) }
+        }
+        // Only one label set is 1 at a time: the previous one is zeroed when the values change.
+        FnReturnTy::Info => quote! {
+            static CURRENT: ::std::sync::Mutex<
+                ::core::option::Option<::std::vec::Vec<(&'static str, ::std::string::String)>>,
+            > = ::std::sync::Mutex::new(::core::option::Option::None);
+
+            let labels: [(&'static str, ::std::string::String); #label_cnt] = [#(#label_emission,)*];
+            let mut current = CURRENT
+                .lock()
+                .unwrap_or_else(::std::sync::PoisonError::into_inner);
+            if let ::core::option::Option::Some(previous) = current.as_ref() {
+                if previous[..] != labels[..] {
+                    ::metrics::gauge!(#metric_name, previous).set(0.0);
+                }
+            }
+            ::metrics::gauge!(#metric_name, &labels).set(1.0);
+            *current = ::core::option::Option::Some(labels.to_vec());
+        },
+        // Setters are serialized, and the current state is set before the others are cleared, so
+        // the set never reads as having no state. Each state is a separate gauge though, so a
+        // scrape in between can see both the previous and the current state at 1.
+        FnReturnTy::StateSet(state_ty) => quote! {
+            static LOCK: ::std::sync::Mutex<()> = ::std::sync::Mutex::new(());
+
+            let #lock_ident = LOCK
+                .lock()
+                .unwrap_or_else(::std::sync::PoisonError::into_inner);
+            let #current_ident = #state_ident.state_name();
+            let #others_ident = <#state_ty>::STATES
+                .iter()
+                .copied()
+                .filter(|#name_ident| *#name_ident != #current_ident);
+            for #name_ident in ::core::iter::once(#current_ident).chain(#others_ident) {
+                let #labels_ident = [#(#label_emission,)* (#metric_name, #name_ident.to_string())];
+                ::metrics::gauge!(#metric_name, &#labels_ident)
+                    .set(if #name_ident == #current_ident { 1.0 } else { 0.0 });
+            }
+        },
+    };
+    let (labels_binding, fn_ret) = match fn_return_ty {
+        FnReturnTy::Info | FnReturnTy::StateSet(_) => (quote! {}, quote! {}),
+        _ => (labels_binding, quote! { #arrow_token #fn_return_ty }),
+    };
+
+    // `#[typed]` metrics return a wrapper instead, only exposing operations that make sense for
+    // their kind, with values typed after their unit.
+    let (typed_wrapper, metric_emission, fn_ret, raw) = match typed {
+        Some(_) => {
+            let wrapper_ident = Ident::new(
+                &format!("{}{}", camel_case(&fn_name), fn_return_ty.type_name()),
+                metric_name_ident.span(),
+            );
+            let wrapper = expand_typed_wrapper(
+                &metric_name,
+                &wrapper_ident,
+                &fn_return_ty,
+                unit.as_ref().and_then(units::Unit::from_expr),
+                &cfg,
+                &fn_vis,
+            );
+            (
+                wrapper,
+                quote! { #wrapper_ident(#metric_emission) },
+                quote! { #arrow_token #wrapper_ident },
+                quote! { .0 },
+            )
+        }
+        None => (quote! {}, metric_emission, fn_ret, quote! {}),
+    };
+
+    let buckets_fn = match buckets {
+        Some(buckets) => {
+            let fn_name = Ident::new(&format!("{fn_name}_buckets"), metric_name_ident.span());
+            let doc = format!("Bucket boundaries of the histogram `{metric_name}`.");
+            let bounds = buckets
+                .bounds
+                .iter()
+                .map(|bound| proc_macro2::Literal::f64_unsuffixed(*bound));
+
+            quote! {
+                #[doc = #doc]
+                #(#cfg)*
+                #fn_vis #fn_token #fn_name() -> &'static [f64] {
+                    &[#(#bounds),*]
+                }
+            }
+        }
+        None => quote! {},
+    };
+
+    // Lets callers, like `#[instrument]`, pass labels by name and have them checked against the
+    // declaration.
+    let labels_struct_ident = Ident::new(
+        &format!("{}Labels", camel_case(&fn_name)),
+        metric_name_ident.span(),
+    );
+    let labels_struct_ty = {
+        let arg_tys = args.iter().map(|arg| &arg.ty);
+        quote! { #labels_struct_ident<#(#arg_tys,)*> }
+    };
+    let labels_fields = quote! { #(labels.#arg_names,)* };
+    let labels_struct = if label_cnt > 0 {
+        let params: Vec<_> = (0..label_cnt)
+            .map(|i| Ident::new(&format!("L{i}"), metric_name_ident.span()))
+            .collect();
+        let struct_doc = format!("Labels of `{metric_name}`, by name.");
+        let with_fn_name = Ident::new(&format!("{fn_name}_with"), metric_name_ident.span());
+        let with_doc = format!("Same as [`{fn_name}`], with labels passed by name.");
+
+        quote! {
+            #[doc = #struct_doc]
+            #(#cfg)*
+            #fn_vis struct #labels_struct_ident<#(#params,)*> {
+                #(pub #arg_names: #params,)*
+            }
+
+            #[doc = #with_doc]
+            #(#cfg)*
+            #fn_vis #fn_token #with_fn_name(labels: #labels_struct_ty #(, #state_param)*) #fn_ret {
+                #metric_name_ident(#labels_fields #state_arg)
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let timer_fns = match (&fn_return_ty, time_unit) {
+        (FnReturnTy::Histogram | FnReturnTy::Summary, Some(time_unit)) => {
+            let start_fn_name = Ident::new(&format!("start_{fn_name}"), metric_name_ident.span());
+            let start_doc = format!(
+                "Starts timing into `{metric_name}`, recording the elapsed {} when the returned \
+                 [`Timer`] is dropped.",
+                time_unit.as_str()
+            );
+            let per_second = proc_macro2::Literal::f64_unsuffixed(
+                time_unit.per_second().expect("time units have a scale"),
+            );
+
+            let time_fn_name = Ident::new(&format!("{fn_name}_time"), metric_name_ident.span());
+            let (time_doc, timer) = match record_cancelled {
+                Some(_) => {
+                    let [completed, cancelled] = FnAttrs::OUTCOMES;
+                    (
+                        format!(
+                            "Times `fut` into `{metric_name}` with `outcome = \"{completed}\"` once \
+                             it completes, or with `outcome = \"{cancelled}\"` if it is dropped \
+                             before completing."
+                        ),
+                        quote! {
+                            Timer::with_outcomes(
+                                #metric_name_ident(#(#arg_names,)*)#raw,
+                                ::core::option::Option::Some(::metrics::histogram!(
+                                    #metric_name,
+                                    &[#(#label_emission,)* ("outcome", #cancelled.to_string())]
+                                )),
+                                #per_second,
+                            )
+                        },
+                    )
+                }
+                None => (
+                    format!(
+                        "Times `fut` into `{metric_name}` once it completes. Futures dropped \
+                         before completing are not recorded."
+                    ),
+                    quote! {
+                        Timer::with_outcomes(
+                            #metric_name_ident(#(#arg_names,)*)#raw,
+                            ::core::option::Option::None,
+                            #per_second,
+                        )
+                    },
+                ),
+            };
+
+            let start_with_fn = if label_cnt > 0 {
+                let fn_name =
+                    Ident::new(&format!("start_{fn_name}_with"), metric_name_ident.span());
+                let doc = format!("Same as [`{start_fn_name}`], with labels passed by name.");
+                quote! {
+                    #[doc = #doc]
+                    #(#cfg)*
+                    #fn_vis #fn_token #fn_name(labels: #labels_struct_ty) -> Timer {
+                        #start_fn_name(#labels_fields)
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            let record_duration_fn_name = Ident::new(
+                &format!("{fn_name}_record_duration"),
+                metric_name_ident.span(),
+            );
+            let record_duration_doc = format!(
+                "Records `duration` into `{metric_name}`, in {}.",
+                time_unit.as_str()
+            );
+            // Can't clash with labels.
+            let [duration_ident, fut_ident, fut_ty_ident, timer_ident, output_ident] =
+                ["duration", "fut", "Fut", "timer", "output"]
+                    .map(|name| Ident::new(name, proc_macro2::Span::mixed_site()));
+
+            quote! {
+                #[doc = #start_doc]
+                #(#cfg)*
+                #fn_vis #fn_token #start_fn_name(#(#fn_args,)*) -> Timer {
+                    Timer::start(#metric_name_ident(#(#arg_names,)*)#raw, #per_second)
+                }
+
+                #start_with_fn
+
+                #[doc = #record_duration_doc]
+                #(#cfg)*
+                #fn_vis #fn_token #record_duration_fn_name(
+                    #(#fn_args,)*
+                    #duration_ident: ::std::time::Duration,
+                ) {
+                    #metric_name_ident(#(#arg_names,)*)#raw
+                        .record(#duration_ident.as_secs_f64() * #per_second);
+                }
+
+                #[doc = #time_doc]
+                #(#cfg)*
+                #fn_vis async #fn_token #time_fn_name<#fut_ty_ident: ::core::future::Future>(
+                    #(#fn_args,)*
+                    #fut_ident: #fut_ty_ident,
+                ) -> #fut_ty_ident::Output {
+                    let #timer_ident = #timer;
+                    let #output_ident = #fut_ident.await;
+                    #timer_ident.stop();
+                    #output_ident
+                }
+            }
+        }
+        _ => quote! {},
+    };
+
+    let track_fns = match fn_return_ty {
+        FnReturnTy::Gauge => {
+            let track_fn_name = Ident::new(&format!("track_{fn_name}"), metric_name_ident.span());
+            let track_doc = format!(
+                "Increments `{metric_name}` until the returned [`InFlightGuard`] is dropped."
+            );
+            let track_with_fn = if label_cnt > 0 {
+                let fn_name =
+                    Ident::new(&format!("track_{fn_name}_with"), metric_name_ident.span());
+                let doc = format!("Same as [`{track_fn_name}`], with labels passed by name.");
+                quote! {
+                    #[doc = #doc]
+                    #(#cfg)*
+                    #fn_vis #fn_token #fn_name(labels: #labels_struct_ty) -> InFlightGuard {
+                        #track_fn_name(#labels_fields)
+                    }
+                }
+            } else {
+                quote! {}
+            };
+
+            quote! {
+                #[doc = #track_doc]
+                #(#cfg)*
+                #fn_vis #fn_token #track_fn_name(#(#fn_args,)*) -> InFlightGuard {
+                    InFlightGuard::new(#metric_name_ident(#(#arg_names,)*)#raw)
+                }
+
+                #track_with_fn
+            }
+        }
+        FnReturnTy::Counter
+        | FnReturnTy::Histogram
+        | FnReturnTy::Summary
+        | FnReturnTy::Info
+        | FnReturnTy::StateSet(_) => quote! {},
+    };
+
+    let build_info_fn = match build_info {
+        Some(build_info) => {
+            let fn_name = Ident::new(&format!("record_{fn_name}"), metric_name_ident.span());
+            let doc = format!(
+                "Sets `{metric_name}` from the crate version and the build environment. Environment \
+                 variables that weren't set at compile time are recorded as `unknown`."
+            );
+            let env_vars = build_info.env_labels.iter().map(|(_, env_var)| env_var);
+
+            quote! {
+                #[doc = #doc]
+                #fn_vis #fn_token #fn_name() {
+                    #metric_name_ident(
+                        ::core::env!("CARGO_PKG_VERSION"),
+                        #(::core::option_env!(#env_vars).unwrap_or("unknown"),)*
+                    )
+                }
+            }
+        }
+        None => quote! {},
     };
 
     // It's kinda odd that the `describe_` macros take in `unit` as the second argument when it is
     // optional, but `description` is mandatory and it is last.
     let description_fn = match description {
         Some(description) => {
-            let fn_name = Ident::new(
-                &format!("describe_{}", &metric_name),
-                metric_name_ident.span(),
-            );
+            let fn_name = Ident::new(&format!("describe_{fn_name}"), metric_name_ident.span());
             let doc = format!("Describes the metric `{metric_name}`.");
 
+            // Short forms like `seconds` expand to their variant; other expressions are kept as
+            // written, so that whatever they refer to is used.
+            let unit = unit.map(|unit| match units::Unit::from_short_form(&unit) {
+                Some(unit) => unit.to_token_stream(),
+                None => unit.to_token_stream(),
+            });
             let description_stmt = match unit {
                 Some(unit) => match fn_return_ty {
                     FnReturnTy::Counter => {
                         quote! { ::metrics::describe_counter!(#metric_name, #unit, #description); }
                     }
-                    FnReturnTy::Gauge => {
+                    FnReturnTy::Gauge | FnReturnTy::Info | FnReturnTy::StateSet(_) => {
                         quote! { ::metrics::describe_gauge!(#metric_name, #unit, #description); }
                     }
-                    FnReturnTy::Histogram => {
+                    FnReturnTy::Histogram | FnReturnTy::Summary => {
                         quote! { ::metrics::describe_histogram!(#metric_name, #unit, #description); }
                     }
                 },
@@ -180,10 +732,10 @@ fn expand_metric_fn(fn_: ItemFn) -> proc_macro2::TokenStream {
                     FnReturnTy::Counter => {
                         quote! { ::metrics::describe_counter!(#metric_name, #description); }
                     }
-                    FnReturnTy::Gauge => {
+                    FnReturnTy::Gauge | FnReturnTy::Info | FnReturnTy::StateSet(_) => {
                         quote! { ::metrics::describe_gauge!(#metric_name,  #description); }
                     }
-                    FnReturnTy::Histogram => {
+                    FnReturnTy::Histogram | FnReturnTy::Summary => {
                         quote! { ::metrics::describe_histogram!(#metric_name, #description); }
                     }
                 },
@@ -191,6 +743,8 @@ fn expand_metric_fn(fn_: ItemFn) -> proc_macro2::TokenStream {
 
             quote! {
                 #[doc = #doc]
+                #[doc = ""]
+                #[doc = #metric_doc]
                 #(#cfg)*
                 #fn_vis #fn_token #fn_name() {
                     #description_stmt
@@ -202,44 +756,970 @@ fn expand_metric_fn(fn_: ItemFn) -> proc_macro2::TokenStream {
 
     quote! {
         #[doc = #doc]
+        #[doc = ""]
+        #[doc = #metric_doc]
         #(#cfg)*
-        #fn_vis #fn_token #metric_name_ident(#(#fn_args,)*) #arrow_token #fn_return_ty {
+        #fn_vis #fn_token #metric_name_ident(#(#fn_args,)* #(#state_param,)*) #fn_ret {
             #labels_binding
             #metric_emission
         }
 
+        #typed_wrapper
+
+        #labels_struct
+
         #description_fn
+
+        #buckets_fn
+
+
+
+        #timer_fns
+
+        #track_fns
+
+        #build_info_fn
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::common::test_utils::code_str;
-    use pretty_assertions::assert_eq;
-    use syn::parse_quote;
+/// Generates the wrapper a `#[typed]` metric function returns.
+fn expand_typed_wrapper(
+    metric_name: &str,
+    wrapper_ident: &Ident,
+    kind: &FnReturnTy,
+    unit: Option<units::Unit>,
+    cfg: &[syn::Attribute],
+    vis: &syn::Visibility,
+) -> proc_macro2::TokenStream {
+    let doc = format!("Handle to `{metric_name}`, returned by the `#[typed]` metric function.");
+    // The type values are passed as, and how to convert them into the metric's unit.
+    let (value_ty, value) = match unit {
+        Some(unit) if unit.per_second().is_some() => {
+            let per_second = proc_macro2::Literal::f64_unsuffixed(unit.per_second().unwrap());
+            (
+                quote! { ::std::time::Duration },
+                quote! { value.as_secs_f64() * #per_second },
+            )
+        }
+        Some(unit) if unit.bytes().is_some() => {
+            let bytes = proc_macro2::Literal::f64_unsuffixed(unit.bytes().unwrap() as f64);
+            (quote! { Bytes }, quote! { value.0 as f64 / #bytes })
+        }
+        _ => (quote! { f64 }, quote! { value }),
+    };
+
+    let methods = match kind {
+        // Counters take whole numbers, so only count in bytes when their unit is bytes.
+        FnReturnTy::Counter => match unit {
+            Some(units::Unit::Bytes) => quote! {
+                #[doc = "Increments the counter by a number of bytes."]
+                pub fn increment(&self, value: Bytes) {
+                    self.0.increment(value.0);
+                }
+            },
+            _ => quote! {
+                #[doc = "Increments the counter."]
+                pub fn increment(&self, value: u64) {
+                    self.0.increment(value);
+                }
+            },
+        },
+        FnReturnTy::Gauge => quote! {
+            #[doc = "Sets the gauge."]
+            pub fn set(&self, value: #value_ty) {
+                self.0.set(#value);
+            }
+
+            #[doc = "Increments the gauge."]
+            pub fn increment(&self, value: #value_ty) {
+                self.0.increment(#value);
+            }
+
+            #[doc = "Decrements the gauge."]
+            pub fn decrement(&self, value: #value_ty) {
+                self.0.decrement(#value);
+            }
+        },
+        FnReturnTy::Histogram | FnReturnTy::Summary => {
+            let record_duration = expand_record_duration(unit);
+            quote! {
+                #[doc = "Records a value."]
+                pub fn record(&self, value: #value_ty) {
+                    self.0.record(#value);
+                }
+
+                #record_duration
+            }
+        }
+        FnReturnTy::Info | FnReturnTy::StateSet(_) => {
+            unreachable!("`#[typed]` is rejected on info metrics and state sets when parsing")
+        }
+    };
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone)]
+        #(#cfg)*
+        #vis struct #wrapper_ident(#kind);
+
+        #(#cfg)*
+        impl #wrapper_ident {
+            #methods
+        }
+    }
+}
+
+/// Generates a `record_duration` method for histogram wrappers, if the unit is a time unit.
+fn expand_record_duration(unit: Option<units::Unit>) -> proc_macro2::TokenStream {
+    let Some((unit, per_second)) = unit.and_then(|unit| Some((unit, unit.per_second()?))) else {
+        return quote! {};
+    };
+    let doc = format!("Records a duration, in {}.", unit.as_str());
+    let per_second = proc_macro2::Literal::f64_unsuffixed(per_second);
+
+    quote! {
+        #[doc = #doc]
+        pub fn record_duration(&self, duration: ::std::time::Duration) {
+            self.0.record(duration.as_secs_f64() * #per_second);
+        }
+    }
+}
+
+/// Generates the `Bytes` type `#[typed]` metrics with a byte unit take, if the module has any.
+fn expand_bytes(fns: &[ItemFn]) -> proc_macro2::TokenStream {
+    let has_byte_metrics = fns.iter().any(|fn_| {
+        fn_.attrs.typed.is_some()
+            && fn_
+                .attrs
+                .unit
+                .as_ref()
+                .and_then(units::Unit::from_expr)
+                .and_then(units::Unit::bytes)
+                .is_some()
+    });
+    if !has_byte_metrics {
+        return quote! {};
+    }
+
+    quote! {
+        #[doc = "A number of bytes, converted into the unit of `#[typed]` metrics when recorded."]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct Bytes(pub u64);
+    }
+}
+
+/// `request_duration` becomes `RequestDuration`.
+fn camel_case(snake_case: &str) -> String {
+    snake_case
+        .split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
+
+/// Generates the `Timer` guard returned by `start_<metric>` and used by `<metric>_time`
+/// functions, if any metric has them.
+/// There is no runtime crate to host it, so it is generated into the module.
+fn expand_timer(fns: &[ItemFn]) -> proc_macro2::TokenStream {
+    let has_timers = fns.iter().any(|fn_| {
+        matches!(
+            fn_.fn_return_ty,
+            FnReturnTy::Histogram | FnReturnTy::Summary
+        ) && fn_
+            .attrs
+            .unit
+            .as_ref()
+            .and_then(units::Unit::from_expr)
+            .and_then(units::Unit::per_second)
+            .is_some()
+    });
+    if !has_timers {
+        return quote! {};
+    }
+
+    quote! {
+        #[doc = "Records the time elapsed since it was started into a histogram, in the histogram's"]
+        #[doc = "unit, when dropped or stopped. Cancelled timers record nothing."]
+        #[must_use = "the timer records when dropped, so dropping it right away records nothing useful"]
+        pub struct Timer {
+            completed: ::core::option::Option<::metrics::Histogram>,
+            dropped: ::core::option::Option<::metrics::Histogram>,
+            start: ::std::time::Instant,
+            per_second: f64,
+        }
+
+        impl Timer {
+            fn start(histogram: ::metrics::Histogram, per_second: f64) -> Self {
+                Self::with_outcomes(
+                    histogram.clone(),
+                    ::core::option::Option::Some(histogram),
+                    per_second,
+                )
+            }
+
+            fn with_outcomes(
+                completed: ::metrics::Histogram,
+                dropped: ::core::option::Option<::metrics::Histogram>,
+                per_second: f64,
+            ) -> Self {
+                Self {
+                    completed: ::core::option::Option::Some(completed),
+                    dropped,
+                    start: ::std::time::Instant::now(),
+                    per_second,
+                }
+            }
+
+            #[doc = "Records the elapsed time now rather than on drop, and returns it."]
+            pub fn stop(mut self) -> ::std::time::Duration {
+                self.dropped = ::core::option::Option::None;
+                let elapsed = self.start.elapsed();
+                if let ::core::option::Option::Some(histogram) = self.completed.take() {
+                    histogram.record(elapsed.as_secs_f64() * self.per_second);
+                }
+                elapsed
+            }
+
+            #[doc = "Drops the timer without recording."]
+            pub fn cancel(mut self) {
+                self.completed = ::core::option::Option::None;
+                self.dropped = ::core::option::Option::None;
+            }
+        }
+
+        impl ::core::ops::Drop for Timer {
+            fn drop(&mut self) {
+                if let ::core::option::Option::Some(histogram) = self.dropped.take() {
+                    histogram.record(self.start.elapsed().as_secs_f64() * self.per_second);
+                }
+            }
+        }
+    }
+}
+
+/// Generates the `InFlightGuard` returned by `track_<metric>` functions, if the module declares any
+/// gauges.
+fn expand_in_flight_guard(fns: &[ItemFn]) -> proc_macro2::TokenStream {
+    if !fns
+        .iter()
+        .any(|fn_| matches!(fn_.fn_return_ty, FnReturnTy::Gauge))
+    {
+        return quote! {};
+    }
+
+    quote! {
+        #[doc = "Keeps a gauge incremented while alive, including when unwinding from a panic."]
+        #[must_use = "the gauge is decremented when the guard is dropped, so dropping it right away tracks nothing"]
+        pub struct InFlightGuard {
+            gauge: ::metrics::Gauge,
+        }
+
+        impl InFlightGuard {
+            fn new(gauge: ::metrics::Gauge) -> Self {
+                gauge.increment(1.0);
+                Self { gauge }
+            }
+        }
+
+        impl ::core::ops::Drop for InFlightGuard {
+            fn drop(&mut self) {
+                self.gauge.decrement(1.0);
+            }
+        }
+    }
+}
+
+/// Generates a function listing the bucket boundaries of every histogram in the module that
+/// declares them, so exporters can be configured in one go, e.g. with
+/// `metrics_exporter_prometheus::PrometheusBuilder::set_buckets_for_metric`.
+fn expand_histogram_buckets(fns: &[ItemFn]) -> proc_macro2::TokenStream {
+    let entries: Vec<_> = fns
+        .iter()
+        .filter(|fn_| fn_.attrs.buckets.is_some())
+        .map(|fn_| {
+            let cfg = &fn_.attrs.cfg;
+            let metric_name = &fn_.metric_name;
+            let buckets_fn_name = Ident::new(&format!("{}_buckets", fn_.ident), fn_.ident.span());
+
+            quote! {
+                #(#cfg)*
+                buckets.push((#metric_name, #buckets_fn_name()));
+            }
+        })
+        .collect();
+    if entries.is_empty() {
+        return quote! {};
+    }
+
+    quote! {
+        #[doc = "Bucket boundaries of this module's histograms, by metric name."]
+        pub fn all_histogram_buckets() -> ::std::vec::Vec<(&'static str, &'static [f64])> {
+            let mut buckets = ::std::vec::Vec::new();
+            #(#entries)*
+            buckets
+        }
+    }
+}
+
+/// Generates functions returning the quantiles and window of the module's summaries, if it has
+/// any, to configure exporters with.
+fn expand_summary_settings(fns: &[ItemFn], args: &MacroArgs) -> proc_macro2::TokenStream {
+    if !fns
+        .iter()
+        .any(|fn_| matches!(fn_.fn_return_ty, FnReturnTy::Summary))
+    {
+        return quote! {};
+    }
+
+    let quantiles = args
+        .quantiles
+        .as_ref()
+        .map_or(
+            &Quantiles::DEFAULT[..],
+            |quantiles| &quantiles.quantiles[..],
+        )
+        .iter()
+        .map(|quantile| proc_macro2::Literal::f64_unsuffixed(*quantile));
+    let (max_age_secs, age_buckets) = args.window.as_ref().map_or(
+        (
+            SummaryWindow::DEFAULT_MAX_AGE_SECS,
+            SummaryWindow::DEFAULT_AGE_BUCKETS,
+        ),
+        |window| (window.max_age_secs, window.age_buckets),
+    );
+    let max_age_secs = proc_macro2::Literal::u64_unsuffixed(max_age_secs);
+    let age_buckets = proc_macro2::Literal::u32_unsuffixed(age_buckets);
+
+    quote! {
+        #[doc = "Quantiles reported by this module's summaries."]
+        pub fn summary_quantiles() -> &'static [f64] {
+            &[#(#quantiles),*]
+        }
+
+        #[doc = "Maximum age of the observations in this module's summaries, and the number of buckets they are expired in."]
+        pub fn summary_window() -> (::std::time::Duration, u32) {
+            (::std::time::Duration::from_secs(#max_age_secs), #age_buckets)
+        }
+    }
+}
+
+/// Generates a function applying the buckets of the module's histograms and the quantiles and
+/// window of its summaries to the exporter set with `exporter = "..."`, since the `metrics`
+/// facade can't carry them to it.
+fn expand_exporter_config(fns: &[ItemFn], args: &MacroArgs) -> proc_macro2::TokenStream {
+    let Some(Exporter::Prometheus) = args.exporter else {
+        return quote! {};
+    };
+
+    let buckets = fns.iter().any(|fn_| fn_.attrs.buckets.is_some()).then(|| {
+        quote! {
+            for (metric_name, buckets) in all_histogram_buckets() {
+                builder = builder.set_buckets_for_metric(
+                    ::metrics_exporter_prometheus::Matcher::Full(metric_name.to_owned()),
+                    buckets,
+                )?;
+            }
+        }
+    });
+    // Summary settings are global to the exporter, so they are left alone without summaries.
+    let summaries = fns
+        .iter()
+        .any(|fn_| matches!(fn_.fn_return_ty, FnReturnTy::Summary))
+        .then(|| {
+            quote! {
+                builder = builder.set_quantiles(summary_quantiles())?;
+                let (max_age, age_buckets) = summary_window();
+                builder = builder.set_bucket_duration(max_age / age_buckets)?;
+                if let ::core::option::Option::Some(age_buckets) =
+                    ::core::num::NonZeroU32::new(age_buckets)
+                {
+                    builder = builder.set_bucket_count(age_buckets);
+                }
+            }
+        });
+
+    quote! {
+        #[doc = "Applies the buckets of this module's histograms, and the quantiles and window of its summaries, to `builder`."]
+        pub fn configure_exporter(
+            builder: ::metrics_exporter_prometheus::PrometheusBuilder,
+        ) -> ::core::result::Result<
+            ::metrics_exporter_prometheus::PrometheusBuilder,
+            ::metrics_exporter_prometheus::BuildError,
+        > {
+            #[allow(unused_mut)]
+            let mut builder = builder;
+            #buckets
+            #summaries
+            ::core::result::Result::Ok(builder)
+        }
+    }
+}
+
+/// Renders the standardized rustdoc line appended to generated functions, so that `cargo doc`
+/// shows what is emitted, e.g.:
+///
+/// ```text
+/// Metric: `task_latency` · Type: Histogram · Unit: milliseconds · Labels: `task_name`
+/// ```
+///
+/// Units that aren't a `metrics::Unit` variant are rendered verbatim, and descriptions only if
+/// they are string literals.
+fn metric_doc(
+    metric_name: &str,
+    fn_return_ty: &FnReturnTy,
+    unit: Option<&Expr>,
+    description: Option<&Expr>,
+    args: &Punctuated<FnArg, Token![,]>,
+) -> String {
+    let mut parts = vec![
+        format!("Metric: `{metric_name}`"),
+        format!("Type: {}", fn_return_ty.type_name()),
+    ];
+    if let Some(unit) = unit {
+        match units::Unit::from_expr(unit) {
+            Some(unit) => parts.push(format!("Unit: {}", unit.as_str())),
+            None => parts.push(format!("Unit: `{}`", unit.to_token_stream())),
+        }
+    }
+    if let Some(Expr::Lit(ExprLit {
+        lit: Lit::Str(description),
+        ..
+    })) = description
+    {
+        parts.push(format!("Description: {}", description.value()));
+    }
+    if !args.is_empty() {
+        let labels: Vec<_> = args.iter().map(|arg| format!("`{}`", arg.ident)).collect();
+        parts.push(format!("Labels: {}", labels.join(", ")));
+    }
+
+    format!(" {}", parts.join(" · "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_utils::code_str;
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    #[test]
+    fn expand_empty() {
+        let src = parse_quote! {
+            #[metrics]
+            mod empty {}
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod empty { }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn expand_counter() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn counter() -> Counter;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `counter` · Type: Counter"]
+                pub fn counter() -> ::metrics::Counter {
+                    ::metrics::counter!("counter",)
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn expand_gauge() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn gauge() -> Gauge;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `gauge` · Type: Gauge"]
+                pub fn gauge() -> ::metrics::Gauge {
+                    ::metrics::gauge!("gauge",)
+                }
+
+                #[doc = "Increments `gauge` until the returned [`InFlightGuard`] is dropped."]
+                pub fn track_gauge() -> InFlightGuard {
+                    InFlightGuard::new(gauge())
+                }
+
+                #[doc = "Keeps a gauge incremented while alive, including when unwinding from a panic."]
+                #[must_use = "the gauge is decremented when the guard is dropped, so dropping it right away tracks nothing"]
+                pub struct InFlightGuard {
+                    gauge: ::metrics::Gauge,
+                }
+
+                impl InFlightGuard {
+                    fn new(gauge: ::metrics::Gauge) -> Self {
+                        gauge.increment(1.0);
+                        Self { gauge }
+                    }
+                }
+
+                impl ::core::ops::Drop for InFlightGuard {
+                    fn drop(&mut self) {
+                        self.gauge.decrement(1.0);
+                    }
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn expand_histogram() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn histogram() -> Histogram;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `histogram` · Type: Histogram"]
+                pub fn histogram() -> ::metrics::Histogram {
+                    ::metrics::histogram!("histogram",)
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn expand_labels() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn counter(label_key: &str) -> Counter;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `counter` · Type: Counter · Labels: `label_key`"]
+                pub fn counter(label_key: &str,) -> ::metrics::Counter {
+                    let labels = [("label_key", label_key.to_string()),];
+                    ::metrics::counter!("counter", &labels)
+                }
+
+                #[doc = "Labels of `counter`, by name."]
+                pub struct CounterLabels<L0,> {
+                    pub label_key: L0,
+                }
+
+                #[doc = "Same as [`counter`], with labels passed by name."]
+                pub fn counter_with(labels: CounterLabels<&str,>) -> ::metrics::Counter {
+                    counter(labels.label_key,)
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn label_values_are_not_forwarded() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn counter(#[values("get", "post")] method: &str) -> Counter;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `counter` · Type: Counter · Labels: `method`"]
+                pub fn counter(method: &str,) -> ::metrics::Counter {
+                    let labels = [("method", method.to_string()),];
+                    ::metrics::counter!("counter", &labels)
+                }
+
+                #[doc = "Labels of `counter`, by name."]
+                pub struct CounterLabels<L0,> {
+                    pub method: L0,
+                }
+
+                #[doc = "Same as [`counter`], with labels passed by name."]
+                pub fn counter_with(labels: CounterLabels<&str,>) -> ::metrics::Counter {
+                    counter(labels.method,)
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn rust_docs_are_forwarded() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                /// Rust docs
+                pub fn counter() -> Counter;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = " Rust docs"]
+                #[doc = ""]
+                #[doc = " Metric: `counter` · Type: Counter"]
+                pub fn counter() -> ::metrics::Counter {
+                    ::metrics::counter!("counter",)
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn multi_line_rust_docs_are_forwarded() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                /// First line.
+                ///
+                /// Second paragraph.
+                pub fn counter() -> Counter;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = " First line.\n\n Second paragraph."]
+                #[doc = ""]
+                #[doc = " Metric: `counter` · Type: Counter"]
+                pub fn counter() -> ::metrics::Counter {
+                    ::metrics::counter!("counter",)
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn unit_and_description() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                /// Rust docs
+                #[description = "metric description"]
+                #[unit = metrics::Unit::Count]
+                pub fn histogram() -> Histogram;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = " Rust docs"]
+                #[doc = ""]
+                #[doc = " Metric: `histogram` · Type: Histogram · Unit: count · Description: metric description"]
+                pub fn histogram() -> ::metrics::Histogram {
+                    ::metrics::histogram!("histogram",)
+                }
+
+                #[doc = "Describes the metric `histogram`."]
+                #[doc = ""]
+                #[doc = " Metric: `histogram` · Type: Histogram · Unit: count · Description: metric description"]
+                pub fn describe_histogram() {
+                    ::metrics::describe_histogram!(
+                        "histogram",
+                        metrics::Unit::Count,
+                        "metric description"
+                    );
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn short_unit_forms() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                #[description = "time spent up"]
+                #[unit = seconds]
+                pub fn uptime() -> Counter;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let describe_stmt = code_str! {
+            ::metrics::describe_counter!("uptime", ::metrics::Unit::Seconds, "time spent up");
+        };
+        assert!(actual.contains(&describe_stmt), "{actual}");
+        assert!(actual.contains("Type: Counter · Unit: seconds"));
+    }
+
+    #[test]
+    fn warnings_use_a_deprecated_item() {
+        let warning = lints::Warning::new(
+            proc_macro2::Span::call_site(),
+            "`requests` has no `#[description]`".to_owned(),
+        );
+        let actual = common::warning(warning).to_string();
+
+        let expected = code_str! {
+            const _: () = {
+                #[deprecated(note = "`requests` has no `#[description]`")]
+                #[allow(non_camel_case_types)]
+                struct necessary_metrics_warning;
+                let _ = necessary_metrics_warning;
+            };
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn unit_suffixes() {
+        let args: MacroArgs = parse_quote! { unit_suffixes };
+        let mut mod_: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                #[description = "latency"]
+                #[unit = seconds]
+                #[buckets(0.1, 1.0)]
+                pub fn latency() -> Histogram;
+
+                #[description = "bytes sent"]
+                #[unit = bytes]
+                pub fn sent_total() -> Counter;
+
+                #[description = "startup time"]
+                #[unit = metrics::Unit::Milliseconds]
+                pub fn startup_ms() -> Gauge;
+            }
+        };
+        mod_.apply_args(&args).unwrap();
+        let names: Vec<_> = mod_
+            .fns
+            .iter()
+            .map(|fn_| fn_.metric_name.as_str())
+            .collect();
+        assert_eq!(names, ["latency_seconds", "sent_bytes_total", "startup_ms"]);
+
+        // Generated items keep the function's name.
+        let actual = expand_from_parsed(mod_).to_string();
+        let metric_fn = code_str! {
+            pub fn sent_total() -> ::metrics::Counter {
+                ::metrics::counter!("sent_bytes_total",)
+            }
+        };
+        assert!(actual.contains(&metric_fn), "{actual}");
+        assert!(actual.contains("pub fn latency_buckets"));
+        assert!(actual.contains(r#"buckets . push (("latency_seconds" , latency_buckets ()))"#));
+    }
+
+    #[test]
+    fn prometheus_conventions() {
+        let args: MacroArgs = parse_quote! { conventions = "prometheus" };
+        let mut mod_: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn requests(route: &str) -> Counter;
+            }
+        };
+        mod_.apply_args(&args).unwrap();
+        let actual = expand_from_parsed(mod_).to_string();
+
+        assert!(
+            actual.contains(r#"counter ! ("requests_total" , & labels)"#),
+            "{actual}"
+        );
+        assert!(actual.contains("pub fn requests (route : & str ,)"));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "OpenTelemetry conventions keep units out of metric names, so they can't be combined with `unit_suffixes`"
+    )]
+    fn open_telemetry_conventions_exclude_unit_suffixes() {
+        let _: MacroArgs = parse_quote! { unit_suffixes, conventions = "opentelemetry" };
+    }
+
+    #[test]
+    fn description_only() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                /// Rust docs
+                #[description = " expression".trim() ]
+                pub fn gauge() -> Gauge;
+            }
+        };
+        let actual = expand_from_parsed(src).to_string();
+
+        let expected = code_str! {
+            #[metrics]
+            mod metrics {
+                #[doc = " Rust docs"]
+                #[doc = ""]
+                #[doc = " Metric: `gauge` · Type: Gauge"]
+                pub fn gauge() -> ::metrics::Gauge {
+                    ::metrics::gauge!("gauge",)
+                }
+
+                #[doc = "Describes the metric `gauge`."]
+                #[doc = ""]
+                #[doc = " Metric: `gauge` · Type: Gauge"]
+                pub fn describe_gauge() {
+                    ::metrics::describe_gauge!(
+                        "gauge",
+                        " expression".trim()
+                    );
+                }
+
+                #[doc = "Increments `gauge` until the returned [`InFlightGuard`] is dropped."]
+                pub fn track_gauge() -> InFlightGuard {
+                    InFlightGuard::new(gauge())
+                }
+
+                #[doc = "Keeps a gauge incremented while alive, including when unwinding from a panic."]
+                #[must_use = "the gauge is decremented when the guard is dropped, so dropping it right away tracks nothing"]
+                pub struct InFlightGuard {
+                    gauge: ::metrics::Gauge,
+                }
+
+                impl InFlightGuard {
+                    fn new(gauge: ::metrics::Gauge) -> Self {
+                        gauge.increment(1.0);
+                        Self { gauge }
+                    }
+                }
+
+                impl ::core::ops::Drop for InFlightGuard {
+                    fn drop(&mut self) {
+                        self.gauge.decrement(1.0);
+                    }
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "Metric description has already been set")]
+    fn description_must_only_be_set_once() {
+        let _mod: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                #[description = "metric description"]
+                #[description = "another metric description"]
+                pub fn counter() -> Counter;
+            }
+        };
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot set metric unit without setting metric description")]
+    fn cannot_set_unit_without_description() {
+        let _mod: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                #[unit = metrics::Unit::Seconds]
+                pub fn histogram() -> Histogram;
+            }
+        };
+    }
+
+    #[test]
+    #[should_panic(expected = "Metric unit has already been set")]
+    fn unit_must_only_be_set_once() {
+        let _mod: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                #[description = "metric description"]
+                #[unit = metrics::Unit::Seconds]
+                #[unit = metrics::Unit::Seconds]
+                pub fn gauge() -> Gauge;
+            }
+        };
+    }
 
     #[test]
-    fn expand_empty() {
+    fn alerts_are_not_forwarded() {
         let src = parse_quote! {
             #[metrics]
-            mod empty {}
+            mod metrics {
+                #[alert(name = "Restarting", expr = "{metric} > 3")]
+                pub fn counter() -> Counter;
+            }
         };
         let actual = expand_from_parsed(src).to_string();
 
         let expected = code_str! {
             #[metrics]
-            mod empty { }
+            mod metrics {
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `counter` · Type: Counter"]
+                pub fn counter() -> ::metrics::Counter {
+                    ::metrics::counter!("counter",)
+                }
+            }
         };
         assert_eq!(actual, expected);
     }
 
     #[test]
-    fn expand_counter() {
+    fn histogram_buckets() {
         let src = parse_quote! {
             #[metrics]
             mod metrics {
-                pub fn counter() -> Counter;
+                #[buckets(0.005, 0.01, 1)]
+                pub fn latency() -> Histogram;
+
+                #[cfg(feature = "exponential")]
+                #[buckets(exponential(1, 2, 3))]
+                pub fn exponential() -> Histogram;
             }
         };
         let actual = expand_from_parsed(src).to_string();
@@ -248,8 +1728,38 @@ mod tests {
             #[metrics]
             mod metrics {
                 #[doc = ""]
-                pub fn counter() -> ::metrics::Counter {
-                    ::metrics::counter!("counter",)
+                #[doc = ""]
+                #[doc = " Metric: `latency` · Type: Histogram"]
+                pub fn latency() -> ::metrics::Histogram {
+                    ::metrics::histogram!("latency",)
+                }
+
+                #[doc = "Bucket boundaries of the histogram `latency`."]
+                pub fn latency_buckets() -> &'static [f64] {
+                    &[0.005, 0.01, 1.0]
+                }
+
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `exponential` · Type: Histogram"]
+                #[cfg(feature = "exponential")]
+                pub fn exponential() -> ::metrics::Histogram {
+                    ::metrics::histogram!("exponential",)
+                }
+
+                #[doc = "Bucket boundaries of the histogram `exponential`."]
+                #[cfg(feature = "exponential")]
+                pub fn exponential_buckets() -> &'static [f64] {
+                    &[1.0, 2.0, 4.0]
+                }
+
+                #[doc = "Bucket boundaries of this module's histograms, by metric name."]
+                pub fn all_histogram_buckets() -> ::std::vec::Vec<(&'static str, &'static [f64])> {
+                    let mut buckets = ::std::vec::Vec::new();
+                    buckets.push(("latency", latency_buckets()));
+                    #[cfg(feature = "exponential")]
+                    buckets.push(("exponential", exponential_buckets()));
+                    buckets
                 }
             }
         };
@@ -257,21 +1767,57 @@ mod tests {
     }
 
     #[test]
-    fn expand_gauge() {
-        let src = parse_quote! {
+    #[should_panic(expected = "Histogram buckets must be sorted in increasing order")]
+    fn histogram_buckets_must_be_sorted() {
+        let _mod: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                #[buckets(0.5, 0.1)]
+                pub fn histogram() -> Histogram;
+            }
+        };
+    }
+
+    #[test]
+    #[should_panic(expected = "Buckets can only be set on histograms")]
+    fn buckets_only_on_histograms() {
+        let _mod: Mod = parse_quote! {
             #[metrics]
             mod metrics {
+                #[buckets(linear(0, 10, 5))]
                 pub fn gauge() -> Gauge;
             }
         };
+    }
+
+    #[test]
+    fn summary() {
+        let src = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn queue_time() -> Summary;
+            }
+        };
         let actual = expand_from_parsed(src).to_string();
 
         let expected = code_str! {
             #[metrics]
             mod metrics {
                 #[doc = ""]
-                pub fn gauge() -> ::metrics::Gauge {
-                    ::metrics::gauge!("gauge",)
+                #[doc = ""]
+                #[doc = " Metric: `queue_time` · Type: Summary"]
+                pub fn queue_time() -> ::metrics::Histogram {
+                    ::metrics::histogram!("queue_time",)
+                }
+
+                #[doc = "Quantiles reported by this module's summaries."]
+                pub fn summary_quantiles() -> &'static [f64] {
+                    &[0.5, 0.9, 0.99]
+                }
+
+                #[doc = "Maximum age of the observations in this module's summaries, and the number of buckets they are expired in."]
+                pub fn summary_window() -> (::std::time::Duration, u32) {
+                    (::std::time::Duration::from_secs(600), 5)
                 }
             }
         };
@@ -279,57 +1825,135 @@ mod tests {
     }
 
     #[test]
-    fn expand_histogram() {
+    fn summary_settings() {
         let src = parse_quote! {
             #[metrics]
             mod metrics {
-                pub fn histogram() -> Histogram;
+                pub fn latency() -> Summary;
             }
         };
-        let actual = expand_from_parsed(src).to_string();
+        let args = parse_quote! {
+            quantiles(0.5, 0.95, 0.999),
+            window(max_age = "1m", age_buckets = 3),
+        };
+        let actual = expand_mod(src, &args, vec![]).to_string();
 
-        let expected = code_str! {
+        assert!(actual.contains(&code_str! { &[0.5, 0.95, 0.999] }));
+        assert!(actual.contains(&code_str! { (::std::time::Duration::from_secs(60), 3) }));
+    }
+
+    #[test]
+    fn prometheus_exporter_config() {
+        let src = parse_quote! {
             #[metrics]
             mod metrics {
-                #[doc = ""]
-                pub fn histogram() -> ::metrics::Histogram {
-                    ::metrics::histogram!("histogram",)
+                #[buckets(0.1, 1.0)]
+                pub fn latency() -> Histogram;
+
+                pub fn queue_time() -> Summary;
+            }
+        };
+        let args = parse_quote! { exporter = "prometheus" };
+        let actual = expand_mod(src, &args, vec![]).to_string();
+
+        let expected = code_str! {
+            #[doc = "Applies the buckets of this module's histograms, and the quantiles and window of its summaries, to `builder`."]
+            pub fn configure_exporter(
+                builder: ::metrics_exporter_prometheus::PrometheusBuilder,
+            ) -> ::core::result::Result<
+                ::metrics_exporter_prometheus::PrometheusBuilder,
+                ::metrics_exporter_prometheus::BuildError,
+            > {
+                #[allow(unused_mut)]
+                let mut builder = builder;
+                for (metric_name, buckets) in all_histogram_buckets() {
+                    builder = builder.set_buckets_for_metric(
+                        ::metrics_exporter_prometheus::Matcher::Full(metric_name.to_owned()),
+                        buckets,
+                    )?;
+                }
+                builder = builder.set_quantiles(summary_quantiles())?;
+                let (max_age, age_buckets) = summary_window();
+                builder = builder.set_bucket_duration(max_age / age_buckets)?;
+                if let ::core::option::Option::Some(age_buckets) =
+                    ::core::num::NonZeroU32::new(age_buckets)
+                {
+                    builder = builder.set_bucket_count(age_buckets);
                 }
+                ::core::result::Result::Ok(builder)
             }
         };
-        assert_eq!(actual, expected);
+        assert!(actual.contains(&expected), "{actual}");
     }
 
     #[test]
-    fn expand_labels() {
+    fn exporter_config_is_opt_in() {
         let src = parse_quote! {
             #[metrics]
             mod metrics {
-                pub fn counter(label_key: &str) -> Counter;
+                #[buckets(0.1, 1.0)]
+                pub fn latency() -> Histogram;
             }
         };
         let actual = expand_from_parsed(src).to_string();
 
-        let expected = code_str! {
+        assert!(!actual.contains("configure_exporter"));
+    }
+
+    #[test]
+    fn timers_on_time_unit_histograms() {
+        let src = parse_quote! {
             #[metrics]
             mod metrics {
-                #[doc = ""]
-                pub fn counter(label_key: &str,) -> ::metrics::Counter {
-                    let labels = [("label_key", label_key.to_string()),];
-                    ::metrics::counter!("counter", &labels)
-                }
+                #[description = "request duration"]
+                #[unit = metrics::Unit::Milliseconds]
+                pub fn request_duration(route: &str) -> Histogram;
+
+                #[description = "payload size"]
+                #[unit = metrics::Unit::Bytes]
+                pub fn payload_size() -> Histogram;
             }
         };
-        assert_eq!(actual, expected);
+        let actual = expand_from_parsed(src).to_string();
+
+        let start_fn = code_str! {
+            #[doc = "Starts timing into `request_duration`, recording the elapsed milliseconds when the returned [`Timer`] is dropped."]
+            pub fn start_request_duration(route: &str,) -> Timer {
+                Timer::start(request_duration(route,), 1000.0)
+            }
+        };
+        assert!(actual.contains(&start_fn), "{actual}");
+        assert!(actual.contains("pub struct Timer"));
+        assert!(
+            actual.contains("pub fn request_duration (route : & str ,) -> :: metrics :: Histogram")
+        );
+        assert!(actual.contains("pub fn request_duration_record_duration"));
+        assert!(actual.contains("pub async fn request_duration_time < Fut"));
+        assert!(!actual.contains("start_payload_size"));
     }
 
     #[test]
-    fn rust_docs_are_forwarded() {
+    #[should_panic(
+        expected = "`#[record_cancelled]` can only be set on histograms and summaries with a time unit"
+    )]
+    fn record_cancelled_needs_a_time_unit() {
+        let _mod: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                #[description = "payload size"]
+                #[unit = metrics::Unit::Bytes]
+                #[record_cancelled]
+                pub fn payload_size() -> Histogram;
+            }
+        };
+    }
+
+    #[test]
+    fn info() {
         let src = parse_quote! {
             #[metrics]
             mod metrics {
-                /// Rust docs
-                pub fn counter() -> Counter;
+                pub fn config_info(profile: &str) -> Info;
             }
         };
         let actual = expand_from_parsed(src).to_string();
@@ -337,9 +1961,36 @@ mod tests {
         let expected = code_str! {
             #[metrics]
             mod metrics {
-                #[doc = " Rust docs"]
-                pub fn counter() -> ::metrics::Counter {
-                    ::metrics::counter!("counter",)
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `config_info` · Type: Info · Labels: `profile`"]
+                pub fn config_info(profile: &str,) {
+                    static CURRENT: ::std::sync::Mutex<
+                        ::core::option::Option<::std::vec::Vec<(&'static str, ::std::string::String)>>,
+                    > = ::std::sync::Mutex::new(::core::option::Option::None);
+
+                    let labels: [(&'static str, ::std::string::String); 1usize] =
+                        [("profile", profile.to_string()),];
+                    let mut current = CURRENT
+                        .lock()
+                        .unwrap_or_else(::std::sync::PoisonError::into_inner);
+                    if let ::core::option::Option::Some(previous) = current.as_ref() {
+                        if previous[..] != labels[..] {
+                            ::metrics::gauge!("config_info", previous).set(0.0);
+                        }
+                    }
+                    ::metrics::gauge!("config_info", &labels).set(1.0);
+                    *current = ::core::option::Option::Some(labels.to_vec());
+                }
+
+                #[doc = "Labels of `config_info`, by name."]
+                pub struct ConfigInfoLabels<L0,> {
+                    pub profile: L0,
+                }
+
+                #[doc = "Same as [`config_info`], with labels passed by name."]
+                pub fn config_info_with(labels: ConfigInfoLabels<&str,>) {
+                    config_info(labels.profile,)
                 }
             }
         };
@@ -347,14 +1998,24 @@ mod tests {
     }
 
     #[test]
-    fn unit_and_description() {
+    #[should_panic(expected = "`build_info` is already declared in this module")]
+    fn build_info_must_not_be_declared_twice() {
+        let args: MacroArgs = parse_quote! { build_info(git_sha = "GIT_SHA") };
+        let mut mod_: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn build_info(version: &str) -> Info;
+            }
+        };
+        mod_.apply_args(&args).unwrap();
+    }
+
+    #[test]
+    fn state_set() {
         let src = parse_quote! {
             #[metrics]
             mod metrics {
-                /// Rust docs
-                #[description = "metric description"]
-                #[unit = metrics::Unit::Count]
-                pub fn histogram() -> Histogram;
+                pub fn circuit_state(breaker: &str) -> StateSet<CircuitState>;
             }
         };
         let actual = expand_from_parsed(src).to_string();
@@ -362,18 +2023,31 @@ mod tests {
         let expected = code_str! {
             #[metrics]
             mod metrics {
-                #[doc = " Rust docs"]
-                pub fn histogram() -> ::metrics::Histogram {
-                    ::metrics::histogram!("histogram",)
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `circuit_state` · Type: StateSet · Labels: `breaker`"]
+                pub fn circuit_state(breaker: &str, state: CircuitState,) {
+                    static LOCK: ::std::sync::Mutex<()> = ::std::sync::Mutex::new(());
+
+                    let _lock = LOCK
+                        .lock()
+                        .unwrap_or_else(::std::sync::PoisonError::into_inner);
+                    let current = state.state_name();
+                    let others = <CircuitState>::STATES.iter().copied().filter(|name| *name != current);
+                    for name in ::core::iter::once(current).chain(others) {
+                        let labels = [("breaker", breaker.to_string()), ("circuit_state", name.to_string())];
+                        ::metrics::gauge!("circuit_state", &labels).set(if name == current { 1.0 } else { 0.0 });
+                    }
                 }
 
-                #[doc = "Describes the metric `histogram`."]
-                pub fn describe_histogram() {
-                    ::metrics::describe_histogram!(
-                        "histogram",
-                        metrics::Unit::Count,
-                        "metric description"
-                    );
+                #[doc = "Labels of `circuit_state`, by name."]
+                pub struct CircuitStateLabels<L0,> {
+                    pub breaker: L0,
+                }
+
+                #[doc = "Same as [`circuit_state`], with labels passed by name."]
+                pub fn circuit_state_with(labels: CircuitStateLabels<&str,>, state: CircuitState) {
+                    circuit_state(labels.breaker, state,)
                 }
             }
         };
@@ -381,13 +2055,23 @@ mod tests {
     }
 
     #[test]
-    fn description_only() {
+    #[should_panic(expected = "State sets label their states with the metric name")]
+    fn state_set_label_cant_share_the_metric_name() {
+        let _mod: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn circuit_state(circuit_state: &str) -> StateSet<CircuitState>;
+            }
+        };
+    }
+
+    #[test]
+    fn typed_counter() {
         let src = parse_quote! {
             #[metrics]
             mod metrics {
-                /// Rust docs
-                #[description = " expression".trim() ]
-                pub fn gauge() -> Gauge;
+                #[typed]
+                pub fn requests() -> Counter;
             }
         };
         let actual = expand_from_parsed(src).to_string();
@@ -395,17 +2079,22 @@ mod tests {
         let expected = code_str! {
             #[metrics]
             mod metrics {
-                #[doc = " Rust docs"]
-                pub fn gauge() -> ::metrics::Gauge {
-                    ::metrics::gauge!("gauge",)
+                #[doc = ""]
+                #[doc = ""]
+                #[doc = " Metric: `requests` · Type: Counter"]
+                pub fn requests() -> RequestsCounter {
+                    RequestsCounter(::metrics::counter!("requests",))
                 }
 
-                #[doc = "Describes the metric `gauge`."]
-                pub fn describe_gauge() {
-                    ::metrics::describe_gauge!(
-                        "gauge",
-                        " expression".trim()
-                    );
+                #[doc = "Handle to `requests`, returned by the `#[typed]` metric function."]
+                #[derive(Clone)]
+                pub struct RequestsCounter(::metrics::Counter);
+
+                impl RequestsCounter {
+                    #[doc = "Increments the counter."]
+                    pub fn increment(&self, value: u64) {
+                        self.0.increment(value);
+                    }
                 }
             }
         };
@@ -413,54 +2102,220 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Metric description has already been set")]
-    fn description_must_only_be_set_once() {
+    #[should_panic(expected = "`#[typed]` metrics need a `metrics::Unit` variant as their unit")]
+    fn typed_metrics_need_a_known_unit() {
         let _mod: Mod = parse_quote! {
             #[metrics]
             mod metrics {
-                #[description = "metric description"]
-                #[description = "another metric description"]
-                pub fn counter() -> Counter;
+                #[description = "latency"]
+                #[unit = my_unit()]
+                #[typed]
+                pub fn latency() -> Histogram;
             }
         };
     }
 
     #[test]
-    #[should_panic(expected = "Cannot set metric unit without setting metric description")]
-    fn cannot_set_unit_without_description() {
+    #[should_panic(expected = "Quantiles must be between 0 and 1")]
+    fn quantiles_must_be_between_0_and_1() {
+        let _args: MacroArgs = parse_quote! { quantiles(0.5, 99.0) };
+    }
+
+    #[test]
+    #[should_panic(expected = "set them with the `quantiles(...)` macro argument")]
+    fn quantiles_are_set_for_the_module() {
         let _mod: Mod = parse_quote! {
             #[metrics]
             mod metrics {
-                #[unit = metrics::Unit::Seconds]
-                pub fn histogram() -> Histogram;
+                #[quantiles(0.5, 0.99)]
+                pub fn summary() -> Summary;
             }
         };
     }
 
     #[test]
-    #[should_panic(expected = "Metric unit has already been set")]
-    fn unit_must_only_be_set_once() {
+    #[should_panic(expected = "Expected a positive duration")]
+    fn window_max_age_must_be_a_duration() {
+        let _args: MacroArgs = parse_quote! { window(max_age = "10 minutes") };
+    }
+
+    #[test]
+    #[should_panic(expected = "Duration is too long")]
+    fn window_max_age_must_not_overflow() {
+        let _args: MacroArgs = parse_quote! { window(max_age = "999999999999999999h") };
+    }
+
+    #[test]
+    #[should_panic(expected = "Alert expression must refer to the metric")]
+    fn alert_must_refer_to_metric() {
         let _mod: Mod = parse_quote! {
             #[metrics]
             mod metrics {
-                #[description = "metric description"]
-                #[unit = metrics::Unit::Seconds]
-                #[unit = metrics::Unit::Seconds]
-                pub fn gauge() -> Gauge;
+                #[alert(name = "Down", expr = "up == 0")]
+                pub fn counter() -> Counter;
             }
         };
     }
 
     #[test]
     #[should_panic(
-        expected = "Only `Counter`, `Gauge`, and `Histogram` (verbatim, no qualified paths) are allowed as return types on functions"
+        expected = "Only `Counter`, `Gauge`, `Histogram`, `Summary`, `Info`, and `StateSet<State>` are allowed as return types on functions, where `Counter`, `Gauge` and `Histogram` may be qualified, as in `metrics::Counter`"
     )]
     fn bad_fn_return_ty() {
         let _mod: Mod = parse_quote! {
             #[metrics]
             mod metrics {
-                pub fn counter() -> metrics::Counter;
+                pub fn counter() -> std::string::String;
+            }
+        };
+    }
+
+    #[test]
+    fn qualified_return_types() {
+        let mut mod_: Mod = parse_quote! {
+            #[metrics]
+            mod metrics {
+                pub fn requests() -> metrics::Counter;
+                pub fn latency() -> ::metrics::Histogram;
+            }
+        };
+        mod_.apply_args(&MacroArgs::default()).unwrap();
+        let actual = expand_from_parsed(mod_).to_string();
+
+        assert!(actual.contains(&code_str! {
+            pub fn requests() -> ::metrics::Counter
+        }));
+        assert!(actual.contains(&code_str! {
+            pub fn latency() -> ::metrics::Histogram
+        }));
+    }
+
+    #[test]
+    fn qualified_return_types_through_a_metrics_alias() {
+        let mod_ = || -> Mod {
+            parse_quote! {
+                #[metrics]
+                mod metrics {
+                    pub fn requests() -> telemetry::metrics::Counter;
+                }
+            }
+        };
+
+        let args: MacroArgs = parse_quote! { metrics_alias = "telemetry::metrics" };
+        mod_().apply_args(&args).unwrap();
+
+        let error = mod_().apply_args(&MacroArgs::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`telemetry::metrics::Counter` isn't a `metrics` type; write `metrics::Counter`, or set `metrics_alias = \"telemetry::metrics\"` if `metrics` is reachable through `telemetry::metrics`"
+        );
+    }
+
+    #[test]
+    fn parse_errors_are_combined() {
+        let tokens = quote! {
+            mod metrics {
+                #[description = "requests"]
+                pub fn requests() -> Counter;
+
+                #[unit = metrics::Unit::Seconds]
+                #[colour = "red"]
+                pub fn latency(#[label] route: &str) -> Histogram;
+
+                #[description = "errors"]
+                pub fn errors() -> metrics::Summary;
+
+                #[description = "connections"]
+                pub fn connections() -> Gauge;
+
+                pub fn queue_length() -> Gauge
+            }
+        };
+        let PartialMod { mod_, errors, .. } = Mod::parse_partial.parse2(tokens).unwrap();
+
+        let names: Vec<_> = mod_.fns.iter().map(|fn_| fn_.ident.to_string()).collect();
+        assert_eq!(names, ["requests", "connections", "queue_length"]);
+        let messages: Vec<_> = errors.unwrap().into_iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Unknown attribute; expected `#[cfg]`, `#[doc]`, `#[description]`, `#[unit]`, `#[alert(...)]`, `#[buckets(...)]`, `#[record_cancelled]`, or `#[typed]`",
+                "Cannot set metric unit without setting metric description",
+                "Only `#[values(...)]` is allowed on function arguments",
+                "Only `Counter`, `Gauge`, `Histogram`, `Summary`, `Info`, and `StateSet<State>` are allowed as return types on functions, where `Counter`, `Gauge` and `Histogram` may be qualified, as in `metrics::Counter`",
+                "expected `;`",
+            ]
+        );
+    }
+
+    #[test]
+    fn stubs_stand_in_for_unparsed_fns() {
+        let tokens = quote! {
+            mod metrics {
+                /// Requests.
+                #[description = "requests"]
+                #[colour = "red"]
+                pub fn requests(#[values("list")] route: &str) -> Counter;
+
+                #[description = "errors"]
+                pub fn errors() -> Countr;
+            }
+        };
+        let partial = Mod::parse_partial.parse2(tokens).unwrap();
+        let stubs: Vec<_> = partial
+            .unparsed_fns
+            .iter()
+            .filter_map(expand_stub)
+            .collect();
+
+        let expected = code_str! {
+            #[doc = r" Requests."]
+            #[allow(unused_variables)]
+            pub fn requests(route: &str,) -> ::metrics::Counter {
+                ::core::unimplemented!("`requests` failed to parse, see the errors of `#[necessary_metrics]`")
+            }
+        };
+        assert_eq!(stubs.len(), 1);
+        assert_eq!(stubs[0].to_string(), expected);
+    }
+
+    #[test]
+    fn items_pass_through() {
+        let mod_: Mod = parse_quote! {
+            mod metrics {
+                use std::fmt;
+
+                const ROUTES: [&str; 2] = ["list", "get"];
+
+                #[description = "requests"]
+                pub fn requests(route: &str) -> Counter;
+
+                pub fn record_request(route: &str) {
+                    requests(route).increment(1);
+                }
+
+                #[cfg(test)]
+                mod tests {}
             }
         };
+
+        let names: Vec<_> = mod_.fns.iter().map(|fn_| fn_.ident.to_string()).collect();
+        assert_eq!(names, ["requests"]);
+        let actual = expand_from_parsed(mod_).to_string();
+        for item in [
+            code_str! { use std::fmt; },
+            code_str! { const ROUTES: [&str; 2] = ["list", "get"]; },
+            code_str! {
+                pub fn record_request(route: &str) {
+                    requests(route).increment(1);
+                }
+            },
+            code_str! {
+                #[cfg(test)]
+                mod tests {}
+            },
+        ] {
+            assert!(actual.contains(&item), "{actual}");
+        }
     }
 }
@@ -0,0 +1,140 @@
+//! `#[derive(StateSet)]`: makes a fieldless enum usable as the states of a `StateSet<State>`
+//! metric.
+//!
+//! There is no runtime crate to host a trait, so the derive generates inherent items instead,
+//! which the code generated for state set metrics calls: `STATES`, every state's name in
+//! declaration order, and `state_name()`. State names are the variant names in snake case.
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, LitStr};
+
+pub(crate) fn expand_derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let DeriveInput {
+        ident,
+        generics,
+        data,
+        ..
+    } = input;
+    let Data::Enum(data) = data else {
+        return Err(syn::Error::new(
+            ident.span(),
+            "`StateSet` can only be derived on enums",
+        ));
+    };
+    if data.variants.is_empty() {
+        return Err(syn::Error::new(
+            ident.span(),
+            "State sets need at least one state",
+        ));
+    }
+
+    let mut variants = Vec::new();
+    let mut names = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new(
+                variant.ident.span(),
+                "States can't have fields",
+            ));
+        }
+        variants.push(&variant.ident);
+        names.push(LitStr::new(
+            &snake_case(&variant.ident.to_string()),
+            variant.ident.span(),
+        ));
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #[doc = "Names of the states, as label values of state set metrics."]
+            pub const STATES: &'static [&'static str] = &[#(#names,)*];
+
+            #[doc = "The name of this state, as a label value of state set metrics."]
+            pub fn state_name(&self) -> &'static str {
+                match self {
+                    #(Self::#variants => #names,)*
+                }
+            }
+        }
+    })
+}
+
+/// `HalfOpen` becomes `half_open`, and `HTTPError` becomes `http_error`.
+fn snake_case(camel_case: &str) -> String {
+    let chars: Vec<char> = camel_case.chars().collect();
+    let mut out = String::with_capacity(camel_case.len() + 4);
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower)
+            {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_utils::code_str;
+    use pretty_assertions::assert_eq;
+    use syn::parse_quote;
+
+    #[test]
+    fn expand_enum() {
+        let input = parse_quote! {
+            enum CircuitState {
+                Closed,
+                HalfOpen,
+                Open,
+            }
+        };
+        let actual = expand_derive(input).unwrap().to_string();
+
+        let expected = code_str! {
+            impl CircuitState {
+                #[doc = "Names of the states, as label values of state set metrics."]
+                pub const STATES: &'static [&'static str] = &["closed", "half_open", "open",];
+
+                #[doc = "The name of this state, as a label value of state set metrics."]
+                pub fn state_name(&self) -> &'static str {
+                    match self {
+                        Self::Closed => "closed",
+                        Self::HalfOpen => "half_open",
+                        Self::Open => "open",
+                    }
+                }
+            }
+        };
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn state_names() {
+        assert_eq!(snake_case("Closed"), "closed");
+        assert_eq!(snake_case("HalfOpen"), "half_open");
+        assert_eq!(snake_case("HTTPError"), "http_error");
+        assert_eq!(snake_case("Retry2Backoff"), "retry2_backoff");
+    }
+
+    #[test]
+    fn states_cant_have_fields() {
+        let input = parse_quote! {
+            enum Connection {
+                Idle,
+                Busy(u32),
+            }
+        };
+        let err = expand_derive(input).unwrap_err();
+        assert_eq!(err.to_string(), "States can't have fields");
+    }
+}
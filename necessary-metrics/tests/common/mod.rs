@@ -0,0 +1,134 @@
+//! A recorder that keeps every value emitted, for asserting on what generated code records.
+// Each test binary only uses some of the accessors.
+#![allow(dead_code)]
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+
+/// Metric values by key, rendered as `name{label=value,...}`.
+#[derive(Clone, Default)]
+pub struct TestRecorder {
+    counters: Arc<Mutex<BTreeMap<String, u64>>>,
+    gauges: Arc<Mutex<BTreeMap<String, f64>>>,
+    histograms: Arc<Mutex<BTreeMap<String, Vec<f64>>>>,
+}
+
+impl TestRecorder {
+    pub fn counter(&self, key: &str) -> Option<u64> {
+        self.counters.lock().unwrap().get(key).copied()
+    }
+
+    pub fn gauge(&self, key: &str) -> Option<f64> {
+        self.gauges.lock().unwrap().get(key).copied()
+    }
+
+    pub fn histogram(&self, key: &str) -> Vec<f64> {
+        self.histograms
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+fn render_key(key: &Key) -> String {
+    let labels: Vec<_> = key
+        .labels()
+        .map(|label| format!("{}={}", label.key(), label.value()))
+        .collect();
+    if labels.is_empty() {
+        key.name().to_owned()
+    } else {
+        format!("{}{{{}}}", key.name(), labels.join(","))
+    }
+}
+
+struct Handle<T> {
+    key: String,
+    values: Arc<Mutex<BTreeMap<String, T>>>,
+}
+
+impl CounterFn for Handle<u64> {
+    fn increment(&self, value: u64) {
+        *self
+            .values
+            .lock()
+            .unwrap()
+            .entry(self.key.clone())
+            .or_default() += value;
+    }
+
+    fn absolute(&self, value: u64) {
+        self.values.lock().unwrap().insert(self.key.clone(), value);
+    }
+}
+
+impl GaugeFn for Handle<f64> {
+    fn increment(&self, value: f64) {
+        *self
+            .values
+            .lock()
+            .unwrap()
+            .entry(self.key.clone())
+            .or_default() += value;
+    }
+
+    fn decrement(&self, value: f64) {
+        *self
+            .values
+            .lock()
+            .unwrap()
+            .entry(self.key.clone())
+            .or_default() -= value;
+    }
+
+    fn set(&self, value: f64) {
+        self.values.lock().unwrap().insert(self.key.clone(), value);
+    }
+}
+
+impl HistogramFn for Handle<Vec<f64>> {
+    fn record(&self, value: f64) {
+        self.values
+            .lock()
+            .unwrap()
+            .entry(self.key.clone())
+            .or_default()
+            .push(value);
+    }
+}
+
+impl Recorder for TestRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        Counter::from_arc(Arc::new(Handle {
+            key: render_key(key),
+            values: self.counters.clone(),
+        }))
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(Arc::new(Handle {
+            key: render_key(key),
+            values: self.gauges.clone(),
+        }))
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(Arc::new(Handle {
+            key: render_key(key),
+            values: self.histograms.clone(),
+        }))
+    }
+}
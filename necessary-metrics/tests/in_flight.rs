@@ -0,0 +1,65 @@
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use common::TestRecorder;
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+mod app_metrics {
+    #[description = "requests in flight"]
+    pub fn requests_in_flight(route: &str) -> Gauge;
+}
+
+#[test]
+fn decrements_on_drop() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        let first = app_metrics::track_requests_in_flight("list");
+        let second = app_metrics::track_requests_in_flight("list");
+        assert_eq!(recorder.gauge("requests_in_flight{route=list}"), Some(2.0));
+
+        drop(first);
+        assert_eq!(recorder.gauge("requests_in_flight{route=list}"), Some(1.0));
+        drop(second);
+    });
+
+    assert_eq!(recorder.gauge("requests_in_flight{route=list}"), Some(0.0));
+}
+
+#[test]
+fn decrements_on_early_return() {
+    fn handle(recorder: &TestRecorder, fail: bool) -> Result<(), ()> {
+        let _guard =
+            app_metrics::track_requests_in_flight_with(app_metrics::RequestsInFlightLabels {
+                route: "get",
+            });
+        assert_eq!(recorder.gauge("requests_in_flight{route=get}"), Some(1.0));
+        if fail {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        assert!(handle(&recorder, true).is_err());
+    });
+
+    assert_eq!(recorder.gauge("requests_in_flight{route=get}"), Some(0.0));
+}
+
+#[test]
+fn decrements_when_unwinding() {
+    let recorder = TestRecorder::default();
+    let result = metrics::with_local_recorder(&recorder, || {
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = app_metrics::track_requests_in_flight("put");
+            assert_eq!(recorder.gauge("requests_in_flight{route=put}"), Some(1.0));
+            panic!("handler panicked");
+        }))
+    });
+
+    assert!(result.is_err());
+    assert_eq!(recorder.gauge("requests_in_flight{route=put}"), Some(0.0));
+}
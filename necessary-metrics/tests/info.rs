@@ -0,0 +1,49 @@
+mod common;
+
+use common::TestRecorder;
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(build_info(git_sha = "NECESSARY_METRICS_TEST_UNSET_GIT_SHA"))]
+mod app_metrics {
+    #[description = "active configuration"]
+    pub fn config_info(profile: &str, region: &str) -> Info;
+}
+
+#[test]
+fn only_the_current_label_set_is_one() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::config_info("dev", "eu");
+        app_metrics::config_info("dev", "eu");
+        assert_eq!(
+            recorder.gauge("config_info{profile=dev,region=eu}"),
+            Some(1.0)
+        );
+
+        app_metrics::config_info("prod", "eu");
+    });
+
+    assert_eq!(
+        recorder.gauge("config_info{profile=dev,region=eu}"),
+        Some(0.0)
+    );
+    assert_eq!(
+        recorder.gauge("config_info{profile=prod,region=eu}"),
+        Some(1.0)
+    );
+}
+
+#[test]
+fn build_info_from_the_environment() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::describe_build_info();
+        app_metrics::record_build_info();
+    });
+
+    let key = format!(
+        "build_info{{version={},git_sha=unknown}}",
+        env!("CARGO_PKG_VERSION")
+    );
+    assert_eq!(recorder.gauge(&key), Some(1.0));
+}
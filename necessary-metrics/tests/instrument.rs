@@ -0,0 +1,117 @@
+mod common;
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use common::TestRecorder;
+use necessary_metrics::{instrument, necessary_metrics};
+
+#[necessary_metrics]
+mod app_metrics {
+    #[description = "request duration"]
+    #[unit = metrics::Unit::Milliseconds]
+    pub fn request_duration(route: &str) -> Histogram;
+
+    #[description = "requests served"]
+    pub fn requests(route: &str, outcome: &str) -> Counter;
+
+    #[description = "tick duration"]
+    #[unit = metrics::Unit::Microseconds]
+    pub fn tick_duration() -> Histogram;
+}
+
+#[instrument(app_metrics::request_duration, labels(route = "parse"), counter = app_metrics::requests)]
+fn parse(s: &str) -> Result<u32, std::num::ParseIntError> {
+    let n = s.parse()?;
+    Ok(n)
+}
+
+#[instrument(app_metrics::tick_duration)]
+async fn tick(n: u32) -> u32 {
+    n + 1
+}
+
+#[instrument(app_metrics::request_duration, labels(route = "digits"))]
+fn digits(n: u8) -> impl Iterator<Item = u8> {
+    0..n
+}
+
+struct Service {
+    prefix: String,
+}
+
+impl Service {
+    #[instrument(app_metrics::request_duration, labels(route = "greet"))]
+    fn greet(&self, name: &str) -> String {
+        if name.is_empty() {
+            return self.prefix.clone();
+        }
+        format!("{} {name}", self.prefix)
+    }
+}
+
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn times_and_counts_outcomes() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        assert_eq!(parse("42"), Ok(42));
+        assert!(parse("forty-two").is_err());
+        assert!(parse("").is_err());
+    });
+
+    assert_eq!(recorder.histogram("request_duration{route=parse}").len(), 3);
+    assert_eq!(
+        recorder.counter("requests{route=parse,outcome=ok}"),
+        Some(1)
+    );
+    assert_eq!(
+        recorder.counter("requests{route=parse,outcome=error}"),
+        Some(2)
+    );
+}
+
+#[test]
+fn times_async_fns() {
+    let recorder = TestRecorder::default();
+    let output = metrics::with_local_recorder(&recorder, || block_on(tick(1)));
+
+    assert_eq!(output, 2);
+    assert_eq!(recorder.histogram("tick_duration").len(), 1);
+}
+
+#[test]
+fn early_returns_are_timed() {
+    let service = Service {
+        prefix: "hello".to_owned(),
+    };
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        assert_eq!(service.greet(""), "hello");
+        assert_eq!(service.greet("world"), "hello world");
+    });
+
+    assert_eq!(recorder.histogram("request_duration{route=greet}").len(), 2);
+}
+
+#[test]
+fn impl_trait_return_types() {
+    let recorder = TestRecorder::default();
+    let digits: Vec<_> = metrics::with_local_recorder(&recorder, || digits(3).collect());
+
+    assert_eq!(digits, [0, 1, 2]);
+    assert_eq!(
+        recorder.histogram("request_duration{route=digits}").len(),
+        1
+    );
+}
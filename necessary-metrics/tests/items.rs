@@ -0,0 +1,62 @@
+mod common;
+
+use common::TestRecorder;
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+mod app_metrics {
+    use metrics::Unit;
+    use necessary_metrics::StateSet;
+
+    const DEFAULT_ROUTE: &str = "index";
+
+    #[derive(StateSet)]
+    pub enum CircuitState {
+        Closed,
+        Open,
+    }
+
+    #[description = "requests"]
+    pub fn requests(route: &str) -> Counter;
+
+    #[description = "request latency"]
+    #[unit = Unit::Seconds]
+    pub fn request_latency() -> Histogram;
+
+    #[description = "circuit breaker state"]
+    pub fn circuit_state() -> StateSet<CircuitState>;
+
+    pub fn record_request(route: Option<&str>) {
+        requests(route.unwrap_or(DEFAULT_ROUTE)).increment(1);
+    }
+
+    #[cfg(test)]
+    mod tests {
+        #[test]
+        fn sees_the_metrics() {
+            let _ = super::requests;
+        }
+    }
+}
+
+#[test]
+fn items_live_next_to_the_metrics() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::record_request(None);
+        app_metrics::record_request(Some("list"));
+        app_metrics::circuit_state(app_metrics::CircuitState::Closed);
+        app_metrics::circuit_state(app_metrics::CircuitState::Open);
+    });
+
+    assert_eq!(recorder.counter("requests{route=index}"), Some(1));
+    assert_eq!(recorder.counter("requests{route=list}"), Some(1));
+    assert_eq!(
+        recorder.gauge("circuit_state{circuit_state=closed}"),
+        Some(0.0)
+    );
+    assert_eq!(
+        recorder.gauge("circuit_state{circuit_state=open}"),
+        Some(1.0)
+    );
+}
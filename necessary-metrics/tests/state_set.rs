@@ -0,0 +1,52 @@
+mod common;
+
+use common::TestRecorder;
+use necessary_metrics::{necessary_metrics, StateSet};
+
+#[derive(StateSet)]
+pub enum CircuitState {
+    Closed,
+    HalfOpen,
+    Open,
+}
+
+#[necessary_metrics]
+mod app_metrics {
+    #[description = "circuit breaker state"]
+    pub fn circuit_state(breaker: &str) -> StateSet<super::CircuitState>;
+
+    #[description = "connection state"]
+    pub fn connection_state(name: &str, current: &str) -> StateSet<super::CircuitState>;
+}
+
+#[test]
+fn only_the_current_state_is_one() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::circuit_state("db", CircuitState::Closed);
+        app_metrics::circuit_state("db", CircuitState::HalfOpen);
+    });
+
+    let state =
+        |name: &str| recorder.gauge(&format!("circuit_state{{breaker=db,circuit_state={name}}}"));
+    assert_eq!(state("closed"), Some(0.0));
+    assert_eq!(state("half_open"), Some(1.0));
+    assert_eq!(state("open"), Some(0.0));
+}
+
+#[test]
+fn labels_are_not_shadowed() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::connection_state("db", "primary", CircuitState::Open);
+    });
+
+    assert_eq!(
+        recorder.gauge("connection_state{name=db,current=primary,connection_state=open}"),
+        Some(1.0)
+    );
+    assert_eq!(
+        recorder.gauge("connection_state{name=db,current=primary,connection_state=closed}"),
+        Some(0.0)
+    );
+}
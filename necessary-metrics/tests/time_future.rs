@@ -0,0 +1,154 @@
+mod common;
+
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+use common::TestRecorder;
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+mod app_metrics {
+    #[description = "request duration"]
+    #[unit = metrics::Unit::Milliseconds]
+    pub fn request_duration(route: &str) -> Histogram;
+
+    #[description = "query duration"]
+    #[unit = metrics::Unit::Seconds]
+    #[record_cancelled]
+    pub fn query_duration(table: &str) -> Histogram;
+
+    #[description = "task duration"]
+    #[unit = metrics::Unit::Seconds]
+    pub fn task_duration(fut: &str, timer: &str, output: &str) -> Histogram;
+}
+
+/// Returns `Pending` once, then its value.
+struct YieldOnce<T>(Option<T>, bool);
+
+impl<T: Unpin> Future for YieldOnce<T> {
+    type Output = T;
+
+    fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        if !self.1 {
+            self.1 = true;
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Poll::Ready(self.0.take().unwrap())
+    }
+}
+
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// Polls the future once, then drops it.
+fn poll_once_then_drop<F: Future>(fut: F) {
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(pin!(fut).poll(&mut cx).is_pending());
+}
+
+#[test]
+fn records_on_completion() {
+    let recorder = TestRecorder::default();
+    let output = metrics::with_local_recorder(&recorder, || {
+        block_on(app_metrics::request_duration_time(
+            "list",
+            YieldOnce(Some(42), false),
+        ))
+    });
+
+    assert_eq!(output, 42);
+    assert_eq!(recorder.histogram("request_duration{route=list}").len(), 1);
+}
+
+#[test]
+fn cancelled_futures_are_not_recorded_by_default() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        poll_once_then_drop(app_metrics::request_duration_time(
+            "list",
+            YieldOnce(Some(()), false),
+        ));
+    });
+
+    assert!(recorder
+        .histogram("request_duration{route=list}")
+        .is_empty());
+}
+
+#[test]
+fn cancellations_are_recorded_with_an_outcome_label() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        block_on(app_metrics::query_duration_time(
+            "users",
+            YieldOnce(Some(()), false),
+        ));
+        poll_once_then_drop(app_metrics::query_duration_time(
+            "users",
+            YieldOnce(Some(()), false),
+        ));
+        poll_once_then_drop(app_metrics::query_duration_time(
+            "users",
+            YieldOnce(Some(()), false),
+        ));
+    });
+
+    assert_eq!(
+        recorder
+            .histogram("query_duration{table=users,outcome=completed}")
+            .len(),
+        1
+    );
+    assert_eq!(
+        recorder
+            .histogram("query_duration{table=users,outcome=cancelled}")
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn labels_are_not_shadowed() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        block_on(app_metrics::task_duration_time(
+            "sync",
+            "wall",
+            "disk",
+            YieldOnce(Some(()), false),
+        ))
+    });
+
+    assert_eq!(
+        recorder
+            .histogram("task_duration{fut=sync,timer=wall,output=disk}")
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn other_recordings_are_completed() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::start_query_duration("users").stop();
+        app_metrics::query_duration_record_duration("users", std::time::Duration::from_secs(1));
+        app_metrics::query_duration("users").record(1.0);
+    });
+
+    assert_eq!(
+        recorder
+            .histogram("query_duration{table=users,outcome=completed}")
+            .len(),
+        3
+    );
+}
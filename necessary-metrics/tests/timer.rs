@@ -0,0 +1,83 @@
+mod common;
+
+use std::time::Duration;
+
+use common::TestRecorder;
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+mod app_metrics {
+    #[description = "request duration"]
+    #[unit = metrics::Unit::Milliseconds]
+    pub fn request_duration(route: &str) -> Histogram;
+
+    #[description = "poll duration"]
+    #[unit = metrics::Unit::Nanoseconds]
+    pub fn poll_duration() -> Histogram;
+
+    #[description = "job duration"]
+    #[unit = metrics::Unit::Seconds]
+    pub fn job_duration() -> Summary;
+}
+
+#[test]
+fn records_on_drop_in_the_declared_unit() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        let _timer = app_metrics::start_request_duration("list");
+        std::thread::sleep(Duration::from_millis(20));
+    });
+
+    let recorded = recorder.histogram("request_duration{route=list}");
+    assert_eq!(recorded.len(), 1);
+    assert!(recorded[0] >= 20.0 && recorded[0] < 20_000.0);
+}
+
+#[test]
+fn stop_records_once_and_returns_the_elapsed_time() {
+    let recorder = TestRecorder::default();
+    let elapsed = metrics::with_local_recorder(&recorder, || {
+        let timer = app_metrics::start_poll_duration();
+        std::thread::sleep(Duration::from_millis(1));
+        timer.stop()
+    });
+
+    let recorded = recorder.histogram("poll_duration");
+    assert_eq!(recorded.len(), 1);
+    assert!((recorded[0] - elapsed.as_nanos() as f64).abs() < 1.0);
+
+    let timer = metrics::with_local_recorder(&recorder, app_metrics::start_job_duration);
+    let elapsed = timer.stop();
+    assert_eq!(recorder.histogram("job_duration"), [elapsed.as_secs_f64()]);
+}
+
+#[test]
+fn cancel_records_nothing() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::start_request_duration("list").cancel();
+    });
+
+    assert!(recorder
+        .histogram("request_duration{route=list}")
+        .is_empty());
+}
+
+#[test]
+fn record_duration_scales_to_the_declared_unit() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::request_duration_record_duration("get", Duration::from_micros(2500));
+        // Still a plain histogram otherwise.
+        let histogram: metrics::Histogram = app_metrics::request_duration("get");
+        histogram.record(4.0);
+
+        app_metrics::job_duration_record_duration(Duration::from_millis(1500));
+    });
+
+    assert_eq!(
+        recorder.histogram("request_duration{route=get}"),
+        [2.5, 4.0]
+    );
+    assert_eq!(recorder.histogram("job_duration"), [1.5]);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::time::Duration;
+
+use common::TestRecorder;
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+mod app_metrics {
+    #[description = "request duration"]
+    #[unit = metrics::Unit::Milliseconds]
+    #[typed]
+    pub fn request_duration(route: &str) -> Histogram;
+
+    #[description = "payload size"]
+    #[unit = metrics::Unit::Kibibytes]
+    #[typed]
+    pub fn payload_size() -> Histogram;
+
+    #[description = "bytes sent"]
+    #[unit = metrics::Unit::Bytes]
+    #[typed]
+    pub fn bytes_sent() -> Counter;
+
+    #[description = "cache size"]
+    #[unit = metrics::Unit::Mebibytes]
+    #[typed]
+    pub fn cache_size() -> Gauge;
+
+    #[description = "requests in flight"]
+    #[typed]
+    pub fn in_flight() -> Gauge;
+}
+
+#[test]
+fn values_are_converted_into_the_declared_unit() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        app_metrics::request_duration("list").record(Duration::from_micros(1500));
+        app_metrics::payload_size().record(app_metrics::Bytes(2048));
+        app_metrics::bytes_sent().increment(app_metrics::Bytes(512));
+        app_metrics::cache_size().set(app_metrics::Bytes(3 << 20));
+    });
+
+    assert_eq!(recorder.histogram("request_duration{route=list}"), [1.5]);
+    assert_eq!(recorder.histogram("payload_size"), [2.0]);
+    assert_eq!(recorder.counter("bytes_sent"), Some(512));
+    assert_eq!(recorder.gauge("cache_size"), Some(3.0));
+}
+
+#[test]
+fn guards_still_work_on_typed_metrics() {
+    let recorder = TestRecorder::default();
+    metrics::with_local_recorder(&recorder, || {
+        let _in_flight = app_metrics::track_in_flight();
+        app_metrics::in_flight().increment(1.0);
+        assert_eq!(recorder.gauge("in_flight"), Some(2.0));
+
+        app_metrics::start_request_duration("get").cancel();
+    });
+
+    assert_eq!(recorder.gauge("in_flight"), Some(1.0));
+}
@@ -0,0 +1,9 @@
+//! Compile-fail tests of the macros' diagnostics, checking their messages and spans against the
+//! `.stderr` files next to each case. After an intended change, regenerate them with
+//! `TRYBUILD=overwrite cargo test --test ui` and review the diff.
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
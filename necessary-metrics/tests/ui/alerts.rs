@@ -0,0 +1,21 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #[alert(expr = "rate(errors[5m]) > 1")]
+    pub fn missing_name() -> Counter;
+
+    #[alert(name = "Errors")]
+    pub fn missing_expr() -> Counter;
+
+    #[alert(name = "Errors", owner = "team")]
+    pub fn unknown_key() -> Counter;
+
+    #[alert(name = "Errors", name = "MoreErrors", expr = "errors > 1")]
+    pub fn key_twice() -> Counter;
+
+    #[alert(name = "Slow", expr = "p99 > 1")]
+    pub fn percentile_on_a_counter() -> Counter;
+}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(conventions = "prometheus", catalog = "alerts")]
+pub mod app_metrics {
+    // Renamed to `requests_total`, which the function name follows.
+    #[description = "requests"]
+    #[alert(name = "Requests", expr = "rate(requests[5m]) > 1")]
+    pub fn requests() -> Counter;
+
+    // Renamed to `errors_total`, but the alert watches another metric.
+    #[description = "errors"]
+    #[alert(name = "Down", expr = "up == 0")]
+    pub fn errors() -> Counter;
+}
+
+// `requests` still expands, so that only the alert of `errors` is reported.
+fn main() {
+    app_metrics::requests().increment(1);
+}
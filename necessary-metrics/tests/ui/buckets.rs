@@ -0,0 +1,39 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #[buckets()]
+    pub fn empty() -> Histogram;
+
+    #[buckets(1.0, 0.5)]
+    pub fn unsorted() -> Histogram;
+
+    #[buckets(0.1, 1e400)]
+    pub fn infinite() -> Histogram;
+
+    #[buckets(0.1, "1")]
+    pub fn not_a_number() -> Histogram;
+
+    #[buckets(quadratic(1, 2, 3))]
+    pub fn unknown_generator() -> Histogram;
+
+    #[buckets(linear(1, 2))]
+    pub fn missing_generator_arg() -> Histogram;
+
+    #[buckets(exponential(1, 0.5, 3))]
+    pub fn shrinking_exponential() -> Histogram;
+
+    #[buckets(linear(1, 0, 3))]
+    pub fn flat_linear() -> Histogram;
+
+    #[buckets(linear(1, 2, 0))]
+    pub fn no_buckets() -> Histogram;
+
+    #[buckets(linear(1, 2, 1.5))]
+    pub fn fractional_count() -> Histogram;
+
+    #[buckets(0.1, 1.0)]
+    pub fn on_a_counter() -> Counter;
+}
+
+fn main() {}
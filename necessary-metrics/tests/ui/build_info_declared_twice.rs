@@ -0,0 +1,12 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(build_info)]
+pub mod metrics {
+    #[description = "build info"]
+    pub fn build_info(version: &str) -> Info;
+}
+
+// The declared metric still expands.
+fn main() {
+    metrics::build_info("1.0.0");
+}
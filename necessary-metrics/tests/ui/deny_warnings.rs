@@ -0,0 +1,18 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(deny_warnings, conventions = "prometheus")]
+pub mod metrics {
+    pub fn requests() -> Counter;
+
+    #[description = "latency"]
+    #[unit = seconds]
+    pub fn latency_ms() -> Histogram;
+
+    #[description = "requests by user"]
+    pub fn user_requests(user_id: u64) -> Counter;
+
+    #[description = "queue length"]
+    pub fn queue_length_total() -> Gauge;
+}
+
+fn main() {}
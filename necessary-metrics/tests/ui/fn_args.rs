@@ -0,0 +1,12 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #[description = "requests"]
+    pub fn unknown_attr(#[label] route: &str) -> Counter;
+
+    #[description = "requests"]
+    pub fn values_twice(#[values("a")] #[values("b")] route: &str) -> Counter;
+}
+
+fn main() {}
@@ -0,0 +1,39 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #[description = "requests"]
+    #[colour = "red"]
+    pub fn unknown_attr() -> Counter;
+
+    #[description = "requests"]
+    #[description = "requests again"]
+    pub fn description_twice() -> Counter;
+
+    #[description = "latency"]
+    #[unit = seconds]
+    #[unit = milliseconds]
+    pub fn unit_twice() -> Histogram;
+
+    #[unit = seconds]
+    pub fn unit_without_description() -> Histogram;
+
+    #[description = "latency"]
+    #[buckets(0.1, 1.0)]
+    #[buckets(0.5, 5.0)]
+    pub fn buckets_twice() -> Histogram;
+
+    #[description = "latency"]
+    #[unit = seconds]
+    #[record_cancelled]
+    #[record_cancelled]
+    pub fn record_cancelled_twice() -> Histogram;
+
+    #[description = "latency"]
+    #[unit = seconds]
+    #[typed]
+    #[typed]
+    pub fn typed_twice() -> Histogram;
+}
+
+fn main() {}
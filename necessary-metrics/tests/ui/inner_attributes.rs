@@ -0,0 +1,13 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #![deny(dead_code)]
+
+    #[description = "requests"]
+    pub fn requests() -> Counter;
+
+    fn unused() {}
+}
+
+fn main() {}
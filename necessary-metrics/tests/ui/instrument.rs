@@ -0,0 +1,16 @@
+use necessary_metrics::{instrument, necessary_metrics};
+
+#[necessary_metrics]
+pub mod metrics {
+    #[description = "request duration"]
+    #[unit = seconds]
+    pub fn request_duration(route: &str) -> Histogram;
+}
+
+#[instrument(metrics::request_duration, labels(route = "list"), retries = 3)]
+fn unknown_argument() {}
+
+#[instrument(metrics::request_duration, labels(outcome = "ok"))]
+fn outcome_label() {}
+
+fn main() {}
@@ -0,0 +1,19 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    use std::time::Instant as Timer;
+
+    pub struct RequestLatencyLabels;
+
+    #[description = "request latency"]
+    #[unit = seconds]
+    pub fn request_latency(route: &str) -> Histogram;
+
+    pub fn start_request_latency() {}
+
+    // Only names in the same namespace clash.
+    pub mod request_latency {}
+}
+
+fn main() {}
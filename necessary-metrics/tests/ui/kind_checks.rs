@@ -0,0 +1,28 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #[typed]
+    pub fn typed_info(version: &str) -> Info;
+
+    #[description = "latency"]
+    #[unit = my_unit()]
+    #[typed]
+    pub fn typed_unknown_unit() -> Histogram;
+
+    #[description = "latency"]
+    #[unit = bytes]
+    #[record_cancelled]
+    pub fn record_cancelled_without_time_unit() -> Histogram;
+
+    #[description = "latency"]
+    #[unit = seconds]
+    #[record_cancelled]
+    pub fn record_cancelled_outcome(outcome: &str) -> Histogram;
+
+    pub fn breaker(breaker: &str) -> StateSet<super::State>;
+}
+
+pub enum State {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(build_info(git_sha = "GIT_SHA", git_sha = "GIT_COMMIT"))]
+pub mod metrics {}
+
+fn main() {}
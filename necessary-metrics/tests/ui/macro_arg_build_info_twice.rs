@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(build_info, build_info(git_sha = "GIT_SHA"))]
+pub mod metrics {}
+
+fn main() {}
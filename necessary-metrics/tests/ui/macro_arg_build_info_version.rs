@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(build_info(version = "VERSION"))]
+pub mod metrics {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(catalog = "csv")]
+pub mod metrics {}
+
+fn main() {}
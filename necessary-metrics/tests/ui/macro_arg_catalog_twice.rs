@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(catalog = "yaml", catalog = "yaml")]
+pub mod metrics {}
+
+fn main() {}
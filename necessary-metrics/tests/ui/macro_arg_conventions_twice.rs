@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(conventions = "prometheus", conventions = "opentelemetry")]
+pub mod metrics {}
+
+fn main() {}
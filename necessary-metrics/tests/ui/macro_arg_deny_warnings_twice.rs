@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(deny_warnings, deny_warnings)]
+pub mod metrics {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(exporter = "statsd")]
+pub mod metrics {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(metrics_alias = "telemetry", metrics_alias = "telemetry")]
+pub mod metrics {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(unit_suffixes, conventions = "opentelemetry")]
+pub mod metrics {}
+
+fn main() {}
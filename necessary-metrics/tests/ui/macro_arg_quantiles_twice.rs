@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(quantiles(0.5), quantiles(0.9))]
+pub mod metrics {}
+
+fn main() {}
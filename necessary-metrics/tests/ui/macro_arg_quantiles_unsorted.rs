@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(quantiles(0.9, 0.5))]
+pub mod metrics {}
+
+fn main() {}
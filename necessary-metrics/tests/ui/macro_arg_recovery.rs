@@ -0,0 +1,12 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(catalg = "json")]
+pub mod app_metrics {
+    #[description = "requests"]
+    pub fn requests() -> Counter;
+}
+
+// The module still expands, so that only the misspelled argument is reported.
+fn main() {
+    app_metrics::requests().increment(1);
+}
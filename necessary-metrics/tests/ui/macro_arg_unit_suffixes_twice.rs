@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(unit_suffixes, unit_suffixes)]
+pub mod metrics {}
+
+fn main() {}
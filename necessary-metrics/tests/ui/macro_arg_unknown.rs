@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(colour = "red")]
+pub mod metrics {}
+
+fn main() {}
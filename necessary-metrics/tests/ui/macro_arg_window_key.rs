@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(window(size = 3))]
+pub mod metrics {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics(window(max_age = "1m"), window(max_age = "5m"))]
+pub mod metrics {}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #[description = "requests"]
+    pub fn requests() -> Counter
+}
+
+fn main() {}
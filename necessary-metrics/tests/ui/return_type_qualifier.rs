@@ -0,0 +1,16 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod app_metrics {
+    #[description = "requests"]
+    pub fn requests() -> prometheus::Counter;
+
+    #[description = "latency"]
+    pub fn latency() -> ::metrics::Histogram;
+}
+
+// Both metrics still expand, so that only the qualifier is reported.
+fn main() {
+    app_metrics::requests().increment(1);
+    app_metrics::latency().record(0.5);
+}
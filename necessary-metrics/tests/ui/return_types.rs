@@ -0,0 +1,21 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #[description = "requests"]
+    pub fn unknown() -> Countr;
+
+    #[description = "requests"]
+    pub fn qualified() -> metrics::Summary;
+
+    #[description = "requests"]
+    pub fn tuple() -> (Counter, Gauge);
+
+    #[description = "state"]
+    pub fn state_set_without_state() -> StateSet;
+
+    #[description = "state"]
+    pub fn state_set_with_two_states() -> StateSet<A, B>;
+}
+
+fn main() {}
@@ -0,0 +1,14 @@
+use necessary_metrics::StateSet;
+
+#[derive(StateSet)]
+pub struct NotAnEnum;
+
+#[derive(StateSet)]
+pub enum NoStates {}
+
+#[derive(StateSet)]
+pub enum WithFields {
+    Open { since: u64 },
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    #[quantiles(0.5, 0.9)]
+    pub fn quantiles_on_a_summary() -> Summary;
+
+    #[window(max_age = "1m")]
+    pub fn window_on_a_summary() -> Summary;
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use necessary_metrics::necessary_metrics;
+
+#[necessary_metrics]
+pub mod metrics {
+    pub fn requests() -> Counter;
+
+    #[description = "latency"]
+    #[unit = seconds]
+    pub fn latency_ms() -> Histogram;
+}
+
+fn main() {
+    // Warnings only show up in compile-fail tests next to an error.
+    let _: () = metrics::requests();
+}